@@ -1,7 +1,7 @@
 use msp::{Conf, MspErr};
 
 fn main() -> Result<(), MspErr> {
-    let server = Conf::create_with_port("www.example.com", 19132);
+    let server = Conf::create_bedrock("www.example.com");
     let info = server.get_bedrock_server_status()?;
 
     println!("{}", info);
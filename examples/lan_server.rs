@@ -12,7 +12,7 @@ fn main() -> Result<(), MspErr> {
     let mut lan_server_map = HashMap::<LanServer, SystemTime>::new();
     let mut outer_loop_time_starter = SystemTime::now();
 
-    let (ter, receiver) = get_lan_server_status(&SocketConf {
+    let (discovery, servers) = get_lan_server_status(&SocketConf {
         read_time_out: Some(Duration::from_millis(SERVER_OFFLINE_TIMEOUT)),
         ..Default::default()
     })?;
@@ -38,12 +38,12 @@ fn main() -> Result<(), MspErr> {
         }
 
         if lan_server_map.len() == MAXIMUM_SERVERS {
-            ter();
+            discovery.stop()?;
 
             break;
         }
 
-        match receiver.recv() {
+        match servers.receiver().recv() {
             Ok(result) => {
                 if let Ok(Some(server)) = result {
                     lan_server_map.insert(server, SystemTime::now());
@@ -53,5 +53,5 @@ fn main() -> Result<(), MspErr> {
         }
     }
 
-    Ok(())
+    discovery.join()
 }
@@ -7,7 +7,7 @@ fn main() -> Result<(), MspErr> {
     assert_eq!(conf.port, 25565);
     assert_eq!(conf.socket_conf, SocketConf::default());
 
-    let conf = Conf::create_with_port("www.example.com", 19132);
+    let conf = Conf::create_bedrock("www.example.com");
     assert_eq!(conf.port, 19132);
 
     let conf = Conf::create_from_str("192.168.1.10:25565")?;
@@ -0,0 +1,131 @@
+use crate::{Conf, MspErr, Server};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// Get the status of many servers in parallel, see [Conf::get_server_status].
+///
+/// At most `concurrency` servers are queried at the same time (at least one), which keeps
+/// the number of open sockets bounded when scanning thousands of hosts. Results are returned
+/// in the same order as `confs`.
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{get_server_status_batch, Conf};
+///
+/// let confs = vec![
+///     Conf::create("www.example.com"),
+///     Conf::create_with_port("www.example.org", 25566),
+/// ];
+///
+/// for result in get_server_status_batch(&confs, 16) {
+///     match result {
+///         Ok(server) => println!("{}", server),
+///         Err(err) => println!("{}", err),
+///     }
+/// }
+/// ```
+pub fn get_server_status_batch(confs: &[Conf], concurrency: usize) -> Vec<Result<Server, MspErr>> {
+    let next_idx = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers = (0..concurrency.clamp(1, confs.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+
+                    // Each worker picks the next conf until all are taken.
+                    loop {
+                        let idx = next_idx.fetch_add(1, Ordering::Relaxed);
+
+                        match confs.get(idx) {
+                            Some(conf) => results.push((idx, conf.get_server_status())),
+                            None => break,
+                        }
+                    }
+
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(results) => results,
+                Err(err) => std::panic::resume_unwind(err),
+            })
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Async version of [get_server_status_batch], requires the `tokio` feature.
+///
+/// Servers are queried on the tokio runtime, so it must be called within one.
+#[cfg(feature = "tokio")]
+pub async fn get_server_status_batch_async(
+    confs: &[Conf],
+    concurrency: usize,
+) -> Vec<Result<Server, MspErr>> {
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let tasks = confs
+        .iter()
+        .map(|conf| {
+            let conf = conf.clone();
+            let semaphore = semaphore.clone();
+
+            tokio::spawn(async move {
+                // The semaphore is never closed, so acquiring cannot fail.
+                let _permit = semaphore.acquire_owned().await;
+
+                conf.get_server_status_async().await
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut results = Vec::with_capacity(tasks.len());
+
+    for task in tasks {
+        results.push(match task.await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(MspErr::InternalErr(format!("Status task failed: {}", err))),
+        });
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod batch_test {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_get_server_status_batch() {
+        assert!(get_server_status_batch(&[], 4).is_empty());
+
+        // Ports with nothing listening on them.
+        let confs = (0..5)
+            .map(|_| {
+                let port = TcpListener::bind("127.0.0.1:0")
+                    .unwrap()
+                    .local_addr()
+                    .unwrap()
+                    .port();
+
+                Conf::create_with_port("127.0.0.1", port)
+            })
+            .collect::<Vec<_>>();
+        let results = get_server_status_batch(&confs, 2);
+
+        assert_eq!(results.len(), confs.len());
+        assert!(results.iter().all(|result| result.is_err()));
+    }
+}
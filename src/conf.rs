@@ -1,12 +1,23 @@
+#[cfg(feature = "tokio")]
+use crate::share::{resolve_conf_addrs_async, resolve_host_async, retry_async};
 use crate::{
-    query, server, BedrockServer, LegacyBetaServer, LegacyServer, MspErr, NettyServer, QueryBasic,
-    QueryFull, Server,
+    dns, query, server,
+    share::{is_same_family, resolve_addrs, resolve_conf_addrs, resolve_host, retry},
+    BedrockServer, LegacyBetaServer, LegacyServer, MspErr, NettyServer, PacketStream,
+    ProtocolSupport, QueryBasic, QueryFull, QueryToken, Server, ServerKind,
 };
+use serde::Serialize;
 use std::{
-    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
-    time::Duration,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+/// Default port of Java Edition servers.
+pub const JAVA_DEFAULT_PORT: u16 = 25565;
+/// Default port of Bedrock Edition servers.
+pub const BEDROCK_DEFAULT_PORT: u16 = 19132;
+
 /// Main struct used for configuring the connection.
 ///
 /// By default, the port number for Java Edition is 25565,
@@ -17,8 +28,86 @@ pub struct Conf {
     pub host: String,
     /// Server port.
     pub port: u16,
+    /// Hostname sent to the server in the handshake packet, defaults to [Conf::host].
+    ///
+    /// Set by [Conf::resolve_srv] to keep the original hostname
+    /// after the connection target has been redirected by SRV record. It can also be set
+    /// to any value for reverse proxies (e.g. BungeeCord) routing by hostname, while the
+    /// connection is still made to [Conf::host]. Appending `\0FML3\0` (or `\0FML2\0`
+    /// before Forge 1.18.2) marks the client as modern Forge, so Forge servers answer
+    /// with their mod list, see [Server::forge_data].
+    pub handshake_host: Option<String>,
+    /// Protocol version sent to the server in the handshake packet.
+    ///
+    /// Defaults to `None`, which sends `-1` as the vanilla client does when pinging to
+    /// determine what version to use. See protocol version [numbers](https://wiki.vg/Protocol_version_numbers).
+    pub protocol_version: Option<i32>,
+    /// Connect to the server through a SOCKS5 proxy, see [ProxyConf].
+    ///
+    /// Only TCP-based protocols are supported, UDP-based protocols (Query and Bedrock)
+    /// return [MspErr::NoImpl] when a proxy is set.
+    pub proxy: Option<ProxyConf>,
     /// See [SocketConf].
     pub socket_conf: SocketConf,
+    /// Server address cached by [Conf::create_resolved], used instead of resolving
+    /// [Conf::host] on every request. Not used when [Conf::proxy] is set.
+    pub resolved_addr: Option<SocketAddr>,
+    /// Return the fields of the status response that can be parsed, instead of failing when
+    /// some cannot, e.g. a malformed favicon or description. The failures are reported in
+    /// [Server::errors]. The default value is false.
+    ///
+    /// Only applies to [Conf::get_server_status] and its variants, invalid JSON still fails.
+    pub lenient: bool,
+    /// Resolve [Conf::host] and [ProxyConf::host] with a custom resolver instead of the
+    /// system one, e.g. for split-horizon DNS or tests. The default value is `None`.
+    ///
+    /// SRV records of [Conf::resolve_srv] are still looked up with the built-in resolver.
+    pub resolver: Option<Resolver>,
+}
+
+/// Custom host name resolver, see [Conf::resolver].
+///
+/// # Example
+///
+/// ```
+/// # use msp::{Conf, Resolver};
+/// # use std::net::SocketAddr;
+/// #
+/// let conf = Conf::builder()
+///     .host("mc.internal")
+///     .resolver(Resolver::new(|host, port| match host {
+///         "mc.internal" => Ok(vec![SocketAddr::from(([10, 0, 0, 2], port))]),
+///         _ => Ok(vec![]),
+///     }))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct Resolver(Arc<ResolveFn>);
+
+type ResolveFn = dyn Fn(&str, u16) -> Result<Vec<SocketAddr>, MspErr> + Send + Sync;
+
+impl Resolver {
+    /// Create a resolver from a closure returning the addresses of the host and port.
+    ///
+    /// An empty list fails with [MspErr::DnsErr]. The closure is called as is in async
+    /// methods as well, so it should not block for long.
+    pub fn new<F>(resolve: F) -> Self
+    where
+        F: Fn(&str, u16) -> Result<Vec<SocketAddr>, MspErr> + Send + Sync + 'static,
+    {
+        Self(Arc::new(resolve))
+    }
+
+    /// Resolve the host and port.
+    pub fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, MspErr> {
+        (self.0)(host, port)
+    }
+}
+
+impl std::fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Resolver")
+    }
 }
 
 /// Additional socket configuration.
@@ -28,12 +117,150 @@ pub struct SocketConf {
     pub read_time_out: Option<Duration>,
     /// Set the write timeout for socket.
     pub write_timeout: Option<Duration>,
+    /// Timeout of connecting to each resolved address of a TCP-based server, without it
+    /// a firewalled host can block for the OS default of tens of seconds.
+    /// The default value is `None`, which means only [SocketConf::overall_timeout] applies.
+    pub connect_timeout: Option<Duration>,
     /// Specify the address for creating a UDP connection.
     /// The default value is [Ipv4Addr::UNSPECIFIED].
     pub rep_udp_ipv4: Ipv4Addr,
+    /// Specify the address for creating a UDP connection to a server that only has an
+    /// IPv6 address, IPv4 is preferred otherwise. The default value is [Ipv6Addr::UNSPECIFIED].
+    pub rep_udp_ipv6: Ipv6Addr,
     /// Specify the port for creating a UDP connection.
-    /// The default value is 8000.
+    /// The default value is 5000. Ports below 1024 are privileged on Unix.
     pub rep_udp_port: u16,
+    /// Number of retries when a status request fails with a transient socket error,
+    /// such as connection reset or timeout. The default value is 0.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each subsequent retry.
+    /// The default value is 100 milliseconds.
+    pub retry_backoff: Duration,
+    /// Deadline of the whole exchange with a TCP-based server (connect, handshake,
+    /// status and ping), no matter how many socket operations it takes.
+    /// The default value is `None`, which means only the socket timeouts apply.
+    pub overall_timeout: Option<Duration>,
+    /// Also listen to the IPv6 multicast group in [get_lan_server_status](crate::get_lan_server_status).
+    /// The default value is false.
+    pub lan_ipv6: bool,
+    /// Size of the buffer LAN broadcasts are received into, in bytes. The default value is
+    /// 1024, the same as the vanilla client.
+    ///
+    /// A broadcast is `[MOTD]<motd>[/MOTD][AD]<port>[/AD]`, so the MOTD must be shorter than
+    /// the size minus 25 bytes, non-ASCII characters take 2 to 4 bytes in UTF-8.
+    /// Broadcasts that do not fit may be truncated, the server is then skipped.
+    pub lan_buffer_size: usize,
+    /// Send `Ok(None)` through the LAN discovery channel whenever [SocketConf::read_time_out]
+    /// passes without a broadcast, see [LanServers](crate::LanServers). The default value is true.
+    ///
+    /// When false, only discovered servers and errors are sent, and a dropped receiver is only
+    /// noticed on the next broadcast, so call [LanDiscovery::stop](crate::LanDiscovery::stop)
+    /// to stop the discovery.
+    pub lan_heartbeat: bool,
+    /// Measure the latency in [Conf::get_server_status] with an extra ping round-trip.
+    /// When false, [Server::ping] is left at 0. The default value is true.
+    pub measure_ping: bool,
+    /// Shut down the TCP connection once the ping of [Conf::get_server_status] or
+    /// [Conf::get_server_latency] is done, instead of just dropping it, which some servers
+    /// log as an abrupt disconnect. The default value is false.
+    pub graceful_close: bool,
+    /// Local address to bind TCP connections to, e.g. to go through a specific interface.
+    /// Only server addresses of the same family are tried.
+    /// The default value is `None`, which lets the OS pick the local address.
+    pub bind_addr: Option<SocketAddr>,
+    /// Order in which the resolved addresses of a TCP-based server are tried.
+    /// The default value is [IpPreference::Resolver].
+    pub ip_preference: IpPreference,
+    /// Number of unconnected pings sent in [Conf::get_bedrock_server_status] before giving up,
+    /// as a single UDP datagram is easily lost. The default value is 3.
+    pub bedrock_ping_attempts: u32,
+    /// Time to wait for the pong before sending the next unconnected ping,
+    /// capped by the read timeout. The default value is 500 milliseconds.
+    pub bedrock_ping_interval: Duration,
+}
+
+/// Which address family to try first when a host resolves to several addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpPreference {
+    /// Keep the order returned by the system resolver.
+    #[default]
+    Resolver,
+    /// Try IPv4 addresses first.
+    Ipv4,
+    /// Try IPv6 addresses first.
+    Ipv6,
+}
+
+/// [SOCKS5](https://datatracker.ietf.org/doc/html/rfc1928) proxy configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyConf {
+    /// Proxy IP address or a domain name.
+    pub host: String,
+    /// Proxy port.
+    pub port: u16,
+    /// Username and password, if the proxy requires authentication.
+    pub credentials: Option<(String, String)>,
+}
+
+/// Server info returned by the `*_with_meta` methods of [Conf], along with
+/// connection details that help to find out why a request is slow.
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusResult<T> {
+    /// Server info, as returned by the method without `_with_meta`.
+    pub data: T,
+    /// Address the request was sent to, which is the proxy when [Conf::proxy] is set.
+    pub resolved_addr: SocketAddr,
+    /// Time taken by the whole call, including DNS resolution and retries.
+    pub elapsed: Duration,
+}
+
+impl<T> StatusResult<T> {
+    fn create(data: T, resolved_addr: SocketAddr, start: Instant) -> Self {
+        Self {
+            data,
+            resolved_addr,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+/// Overview of a modern Java Edition server returned by [Conf::snapshot], for dashboards.
+///
+/// Failures are recorded in [ServerSnapshot::error] instead of being returned,
+/// the other fields are then `None`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServerSnapshot {
+    /// Whether the server answered with a valid status response.
+    pub online: bool,
+    /// Round-trip latency, `None` if offline or [SocketConf::measure_ping] is false.
+    pub latency: Option<Duration>,
+    /// Online and max players.
+    pub players: Option<(i32, i32)>,
+    /// Version name, see [Version::name](crate::Version::name).
+    pub version: Option<String>,
+    /// Why the server is considered offline.
+    pub error: Option<String>,
+}
+
+impl ServerSnapshot {
+    fn create(result: Result<Server, MspErr>, measure_ping: bool) -> Self {
+        match result {
+            Ok(server) => Self {
+                online: true,
+                latency: measure_ping.then(|| Duration::from_millis(server.ping)),
+                players: Some((server.players.online, server.players.max)),
+                version: Some(server.version.name),
+                error: None,
+            },
+            Err(err) => Self {
+                online: false,
+                latency: None,
+                players: None,
+                version: None,
+                error: Some(err.to_string()),
+            },
+        }
+    }
 }
 
 impl Default for SocketConf {
@@ -41,25 +268,175 @@ impl Default for SocketConf {
         Self {
             read_time_out: None,
             write_timeout: None,
+            connect_timeout: None,
             rep_udp_ipv4: Ipv4Addr::UNSPECIFIED,
+            rep_udp_ipv6: Ipv6Addr::UNSPECIFIED,
             rep_udp_port: 5000,
+            lan_ipv6: false,
+            lan_buffer_size: 1024,
+            lan_heartbeat: true,
+            max_retries: 0,
+            retry_backoff: Duration::from_millis(100),
+            overall_timeout: None,
+            ip_preference: IpPreference::Resolver,
+            measure_ping: true,
+            graceful_close: false,
+            bind_addr: None,
+            bedrock_ping_attempts: 3,
+            bedrock_ping_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Builder of [Conf], see [Conf::builder].
+///
+/// # Example
+///
+/// ```
+/// # use msp::ConfBuilder;
+/// # use std::time::Duration;
+/// #
+/// let conf = ConfBuilder::new()
+///     .host("www.example.com")
+///     .port(25566)
+///     .read_timeout(Duration::from_secs(3))
+///     .build();
+/// #
+/// # assert_eq!(conf.host, "www.example.com");
+/// # assert_eq!(conf.port, 25566);
+/// # assert_eq!(conf.socket_conf.read_time_out, Some(Duration::from_secs(3)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfBuilder {
+    conf: Conf,
+}
+
+impl Default for ConfBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfBuilder {
+    /// Create a builder with the same defaults as [Conf::create].
+    pub fn new() -> Self {
+        Self {
+            conf: Conf::create(""),
         }
     }
+
+    /// Set [Conf::host].
+    pub fn host(mut self, host: &str) -> Self {
+        self.conf.host = host.trim().into();
+        self
+    }
+
+    /// Set [Conf::port].
+    pub fn port(mut self, port: u16) -> Self {
+        self.conf.port = port;
+        self
+    }
+
+    /// Set [SocketConf::read_time_out].
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.conf.socket_conf.read_time_out = Some(timeout);
+        self
+    }
+
+    /// Set [SocketConf::write_timeout].
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.conf.socket_conf.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [SocketConf::rep_udp_port].
+    pub fn udp_port(mut self, port: u16) -> Self {
+        self.conf.socket_conf.rep_udp_port = port;
+        self
+    }
+
+    /// Set [Conf::handshake_host].
+    pub fn handshake_host(mut self, handshake_host: &str) -> Self {
+        self.conf.handshake_host = Some(handshake_host.into());
+        self
+    }
+
+    /// Set [Conf::proxy].
+    pub fn proxy(mut self, proxy: ProxyConf) -> Self {
+        self.conf.proxy = Some(proxy);
+        self
+    }
+
+    /// Set [Conf::lenient].
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.conf.lenient = lenient;
+        self
+    }
+
+    /// Set [Conf::resolver].
+    pub fn resolver(mut self, resolver: Resolver) -> Self {
+        self.conf.resolver = Some(resolver);
+        self
+    }
+
+    /// Build the [Conf].
+    pub fn build(self) -> Conf {
+        self.conf
+    }
 }
 
 impl ToSocketAddrs for Conf {
     type Iter = std::vec::IntoIter<SocketAddr>;
 
     fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
-        (&*self.host, self.port).to_socket_addrs()
+        match (self.resolved_addr, &self.resolver) {
+            (Some(addr), _) => Ok(vec![addr].into_iter()),
+            (None, Some(resolver)) => resolver
+                .resolve(&self.host, self.port)
+                .map(Vec::into_iter)
+                .map_err(|err| std::io::Error::other(err.to_string())),
+            (None, None) => (&*self.host, self.port).to_socket_addrs(),
+        }
+    }
+}
+
+/// Parse `host:port`, see [Conf::create_from_str].
+///
+/// # Example
+///
+/// ```
+/// # use msp::{Conf, MspErr};
+/// #
+/// # fn main() -> Result<(), MspErr> {
+/// let conf = "www.example.com:25566".parse::<Conf>()?;
+/// #
+/// # assert_eq!(conf.port, 25566);
+/// # assert_eq!(Conf::try_from("[::1]:25565")?.host, "::1");
+/// # Ok(())
+/// # }
+/// ```
+impl std::str::FromStr for Conf {
+    type Err = MspErr;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        Self::create_from_str(addr)
+    }
+}
+
+/// Same as [Conf::create_from_str].
+impl TryFrom<&str> for Conf {
+    type Error = MspErr;
+
+    fn try_from(addr: &str) -> Result<Self, Self::Error> {
+        Self::create_from_str(addr)
     }
 }
 
 impl Conf {
     /// Create a connection configuration using the default port.
     ///
-    /// Default port is based on Java Edition(25565), to create a default port based on
-    /// Bedrock Edition(19132), use [Conf::create_with_port] to manually specify it.
+    /// Default port is based on Java Edition([JAVA_DEFAULT_PORT]), to create a default port based on
+    /// Bedrock Edition([BEDROCK_DEFAULT_PORT]), use [Conf::create_bedrock].
     ///
     /// # Examples
     ///
@@ -70,16 +447,60 @@ impl Conf {
     /// #
     /// # assert_eq!(conf.host, "www.example.com");
     /// # assert_eq!(conf.port, 25565);
+    /// # assert_eq!(conf.protocol_version, None);
     /// # assert_eq!(conf.socket_conf, SocketConf::default());
     /// ```
     pub fn create(host: &str) -> Self {
         Self {
             host: host.trim().into(),
-            port: 25565,
+            port: JAVA_DEFAULT_PORT,
+            handshake_host: None,
+            protocol_version: None,
+            proxy: None,
             socket_conf: SocketConf::default(),
+            resolved_addr: None,
+            lenient: false,
+            resolver: None,
         }
     }
 
+    /// Create a connection configuration using the default port of Bedrock Edition,
+    /// see [BEDROCK_DEFAULT_PORT].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use msp::{Conf, BEDROCK_DEFAULT_PORT};
+    /// #
+    /// let conf = Conf::create_bedrock("www.example.com");
+    /// #
+    /// # assert_eq!(conf.port, BEDROCK_DEFAULT_PORT);
+    /// ```
+    pub fn create_bedrock(host: &str) -> Self {
+        Self::create_with_port(host, BEDROCK_DEFAULT_PORT)
+    }
+
+    /// Create a [ConfBuilder] to configure the connection with chainable methods.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use msp::Conf;
+    /// # use std::time::Duration;
+    /// #
+    /// let conf = Conf::builder()
+    ///     .host("www.example.com")
+    ///     .write_timeout(Duration::from_secs(3))
+    ///     .udp_port(8000)
+    ///     .build();
+    /// #
+    /// # assert_eq!(conf.port, 25565);
+    /// # assert_eq!(conf.socket_conf.rep_udp_port, 8000);
+    /// ```
+    pub fn builder() -> ConfBuilder {
+        ConfBuilder::new()
+    }
+
     /// Create a connection configuration using the specified port.
     ///
     /// # Example
@@ -95,10 +516,46 @@ impl Conf {
         Self {
             host: host.trim().into(),
             port,
+            handshake_host: None,
+            protocol_version: None,
+            proxy: None,
             socket_conf: SocketConf::default(),
+            resolved_addr: None,
+            lenient: false,
+            resolver: None,
         }
     }
 
+    /// Create a connection configuration using the specified port, with the host resolved once.
+    ///
+    /// The first resolved address is cached in [Conf::resolved_addr], so that reusing the
+    /// [Conf] does not look up the host again, e.g. when polling a server in a tight loop.
+    /// Note that Bedrock and Query requests otherwise prefer an IPv4 address.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use msp::{Conf, MspErr};
+    /// # use std::net::ToSocketAddrs;
+    /// #
+    /// # fn main() -> Result<(), MspErr> {
+    /// let conf = Conf::create_resolved("127.0.0.1", 25565)?;
+    /// #
+    /// # assert_eq!(conf.resolved_addr, Some("127.0.0.1:25565".parse().unwrap()));
+    /// # assert_eq!(conf.to_socket_addrs()?.count(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_resolved(host: &str, port: u16) -> Result<Self, MspErr> {
+        let mut conf = Self::create_with_port(host, port);
+
+        conf.resolved_addr = resolve_addrs(&conf.host, port, IpPreference::Resolver)?
+            .first()
+            .copied();
+
+        Ok(conf)
+    }
+
     /// Create a connection configuration by using a string.
     ///
     /// Attempting to split the given string into two parts,
@@ -106,6 +563,9 @@ impl Conf {
     /// the second part being the port of the server. If the port
     /// cannot be converted to [u16], it will throw a [MspErr] error.
     ///
+    /// IPv6 addresses are accepted in the bracketed form (`[::1]:25565`), or bare
+    /// (`::1`, `[::1]`) in which case the port defaults to 25565.
+    ///
     /// # Example
     ///
     /// ```
@@ -121,10 +581,47 @@ impl Conf {
     /// #   assert!(conf.is_err());
     /// #   let conf = Conf::create_from_str("www.example.com:-1");
     /// #   assert!(conf.is_err());
+    /// #   let conf = Conf::create_from_str("[::1]:25566")?;
+    /// #   assert_eq!((conf.host.as_str(), conf.port), ("::1", 25566));
+    /// #   let conf = Conf::create_from_str("2001:db8::1")?;
+    /// #   assert_eq!((conf.host.as_str(), conf.port), ("2001:db8::1", 25565));
+    /// #   assert!(Conf::create_from_str("[::1").is_err());
+    /// #   assert!(Conf::create_from_str("[www.example.com]:25565").is_err());
     /// #   Ok(())
     /// # }
     /// ```
     pub fn create_from_str(addr: &str) -> Result<Self, MspErr> {
+        let addr = addr.trim();
+
+        // Bracketed IPv6 literal, with an optional port: [::1]:25565
+        if let Some(rest) = addr.strip_prefix('[') {
+            let (host, port) = match rest.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => match port.strip_prefix(':') {
+                    Some(port) => (host, Some(port)),
+                    None => {
+                        return Err(MspErr::DataErr(format!(
+                            "Invalid IPv6 socket address syntax: {}",
+                            addr
+                        )));
+                    }
+                },
+                None => {
+                    return Err(MspErr::DataErr(format!(
+                        "Missing closing bracket in IPv6 socket address: {}",
+                        addr
+                    )));
+                }
+            };
+
+            return Self::create_from_ipv6(host, port);
+        }
+
+        // Bare IPv6 literal without port: ::1
+        if addr.matches(':').count() > 1 {
+            return Self::create_from_ipv6(addr, None);
+        }
+
         let addr_split = addr.split(":").map(|x| x.trim()).collect::<Vec<_>>();
 
         if addr_split.len() != 2 {
@@ -134,13 +631,89 @@ impl Conf {
             )));
         }
 
-        match addr_split[1].parse::<u16>() {
-            Ok(port) => Ok(Self {
-                host: addr_split[0].into(),
-                port,
-                socket_conf: SocketConf::default(),
+        Ok(Self::create_with_port(
+            addr_split[0],
+            Self::parse_port(addr_split[1])?,
+        ))
+    }
+
+    fn create_from_ipv6(host: &str, port: Option<&str>) -> Result<Self, MspErr> {
+        if host.parse::<Ipv6Addr>().is_err() {
+            return Err(MspErr::DataErr(format!("Invalid IPv6 address: {}", host)));
+        }
+
+        match port {
+            Some(port) => Ok(Self::create_with_port(host, Self::parse_port(port)?)),
+            None => Ok(Self::create(host)),
+        }
+    }
+
+    fn parse_port(port: &str) -> Result<u16, MspErr> {
+        port.trim()
+            .parse::<u16>()
+            .map_err(|_| MspErr::DataErr(format!("Invalid port: {}", port)))
+    }
+
+    /// Resolve the `_minecraft._tcp` [SRV record](https://wiki.vg/Server_List_Ping#SRV_Record) of the host.
+    ///
+    /// Many Java Edition servers publish an SRV record that points the real hostname and port
+    /// somewhere else, the vanilla client follows it before connecting. Return a new [Conf] with
+    /// the resolved host and port, the original hostname is kept in [Conf::handshake_host] and is
+    /// still sent in the handshake. If the host is an IP address or no record exists, a copy of
+    /// the original configuration is returned.
+    ///
+    /// The nameservers are read from `/etc/resolv.conf` and tried in order. On platforms
+    /// without it, SRV records are not looked up and a copy of the original configuration
+    /// is returned as well.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com").resolve_srv()?;
+    ///     let info = server.get_server_status()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn resolve_srv(&self) -> Result<Conf, MspErr> {
+        if self.host.parse::<IpAddr>().is_ok() {
+            return Ok(self.clone());
+        }
+
+        let record = match dns::resolve_srv_record(
+            &format!("_minecraft._tcp.{}", self.host),
+            &self.socket_conf,
+        ) {
+            Ok(record) => record,
+            Err(MspErr::NoImpl(reason)) => {
+                log::debug!("Skip SRV lookup: {}", reason);
+
+                None
+            }
+            Err(err) => return Err(err),
+        };
+
+        match record {
+            Some(record) => Ok(Conf {
+                host: record.target,
+                port: record.port,
+                handshake_host: Some(
+                    self.handshake_host
+                        .clone()
+                        .unwrap_or_else(|| self.host.clone()),
+                ),
+                protocol_version: self.protocol_version,
+                proxy: self.proxy.clone(),
+                socket_conf: self.socket_conf.clone(),
+                // The cached address belongs to the SRV host, not to the target.
+                resolved_addr: None,
+                lenient: self.lenient,
+                resolver: self.resolver.clone(),
             }),
-            Err(_) => Err(MspErr::DataErr(format!("Invalid port: {}", addr_split[1]))),
+            None => Ok(self.clone()),
         }
     }
 
@@ -162,7 +735,138 @@ impl Conf {
     /// }
     /// ```
     pub fn get_server_status(&self) -> Result<Server, MspErr> {
-        server::get_server_status(self)
+        retry(&self.socket_conf, || server::get_server_status(self))
+    }
+
+    /// Get the untyped JSON status of a modern Java Edition server.
+    ///
+    /// Same as [Conf::get_server_status], but returns the status response as is, which
+    /// gives access to fields not modeled by [Server], such as custom keys of modded servers.
+    /// The latency is not measured.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let info = server.get_server_status_raw()?;
+    ///     let previews_chat = info["previewsChat"].as_bool();
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_server_status_raw(&self) -> Result<serde_json::Value, MspErr> {
+        retry(&self.socket_conf, || server::get_server_status_raw(self))
+    }
+
+    /// Get the status response of a modern Java Edition server as the UTF-8 JSON bytes
+    /// sent by the server, before any parsing.
+    ///
+    /// Unlike serializing [Server] again, the field order and whitespace are kept,
+    /// e.g. to cache and replay the response verbatim. The latency is not measured.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let bytes = server.get_server_status_bytes()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_server_status_bytes(&self) -> Result<Vec<u8>, MspErr> {
+        retry(&self.socket_conf, || server::get_server_status_bytes(self))
+    }
+
+    /// Get only the online and max player counts of a modern Java Edition server.
+    ///
+    /// Same exchange as [Conf::get_server_status], but only the `players.online` and
+    /// `players.max` fields are parsed, which is cheaper for high-frequency polling.
+    /// The latency is not measured.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let (online, max) = server.get_player_counts()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_player_counts(&self) -> Result<(i32, i32), MspErr> {
+        retry(&self.socket_conf, || server::get_player_counts(self))
+    }
+
+    /// Whether a modern Java Edition server is reachable.
+    ///
+    /// Return true if the server answers the handshake and status request with a
+    /// status response, any error is treated as offline. Use [Conf::get_server_status]
+    /// to find out why a server is not reachable.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::Conf;
+    ///
+    /// let server = Conf::create("www.example.com");
+    ///
+    /// if !server.is_online() {
+    ///     println!("Server is offline");
+    /// }
+    /// ```
+    pub fn is_online(&self) -> bool {
+        retry(&self.socket_conf, || server::check_server_online(self)).is_ok()
+    }
+
+    /// Get the online state, latency, player counts and version of a modern Java Edition
+    /// server with a single status request, see [ServerSnapshot].
+    ///
+    /// Never fails, errors are recorded in [ServerSnapshot::error].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::Conf;
+    ///
+    /// let snapshot = Conf::create("www.example.com").snapshot();
+    ///
+    /// match snapshot.players {
+    ///     Some((online, max)) => println!("{}/{} players", online, max),
+    ///     None => println!("Offline: {}", snapshot.error.unwrap_or_default()),
+    /// }
+    /// ```
+    pub fn snapshot(&self) -> ServerSnapshot {
+        ServerSnapshot::create(self.get_server_status(), self.socket_conf.measure_ping)
+    }
+
+    /// Measure the latency of a modern Java Edition server.
+    ///
+    /// Performs the same [Server List Ping](https://wiki.vg/Server_List_Ping#Current_.281.7.2B.29)
+    /// exchange as [Conf::get_server_status], but only returns the round-trip time of the
+    /// ping request, without parsing the status response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let latency = server.ping()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn ping(&self) -> Result<Duration, MspErr> {
+        retry(&self.socket_conf, || server::get_server_latency(self))
     }
 
     /// Get info from a legacy Java Edition server.
@@ -184,7 +888,7 @@ impl Conf {
     /// }
     /// ```
     pub fn get_netty_server_status(&self) -> Result<NettyServer, MspErr> {
-        server::get_netty_server_status(self)
+        retry(&self.socket_conf, || server::get_netty_server_status(self))
     }
 
     /// Get info from a legacy Java Edition server.
@@ -203,7 +907,7 @@ impl Conf {
     /// }
     /// ```
     pub fn get_legacy_server_status(&self) -> Result<LegacyServer, MspErr> {
-        server::get_legacy_server_status(self)
+        retry(&self.socket_conf, || server::get_legacy_server_status(self))
     }
 
     /// Get info from a beta legacy Java Edition server in beta release.
@@ -224,7 +928,9 @@ impl Conf {
     /// }
     /// ```
     pub fn get_beta_legacy_server_status(&self) -> Result<LegacyBetaServer, MspErr> {
-        server::get_beta_legacy_server_status(&self)
+        retry(&self.socket_conf, || {
+            server::get_beta_legacy_server_status(self)
+        })
     }
 
     /// Get **basic** info from a modern Java Edition server using the [Query](https://wiki.vg/Query) protocol.
@@ -245,7 +951,7 @@ impl Conf {
     /// }
     /// ```
     pub fn query(&self) -> Result<QueryBasic, MspErr> {
-        query::query_basic_status(self)
+        retry(&self.socket_conf, || query::query_basic_status(self))
     }
 
     /// Get **full** info from a modern Java Edition server using the [Query](https://wiki.vg/Query) protocol.
@@ -266,7 +972,91 @@ impl Conf {
     /// }
     /// ```
     pub fn query_full(&self) -> Result<QueryFull, MspErr> {
-        query::query_full_status(self)
+        retry(&self.socket_conf, || query::query_full_status(self))
+    }
+
+    /// Get only the online player names from a modern Java Edition server using the [Query](https://wiki.vg/Query) protocol.
+    ///
+    /// Same request as [Conf::query_full], but the other fields are not kept. Unlike the
+    /// sample of [Conf::get_server_status], the list is complete.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create_with_port("www.example.com", 25565);
+    ///     let players = server.query_players()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_players(&self) -> Result<Vec<String>, MspErr> {
+        retry(&self.socket_conf, || query::query_players(self))
+    }
+
+    /// Get both **basic** and **full** info from a modern Java Edition server using the [Query](https://wiki.vg/Query) protocol.
+    ///
+    /// Same as calling [Conf::query] and [Conf::query_full], but both stat requests
+    /// reuse the challenge token of a single handshake, which saves a round-trip.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create_with_port("www.example.com", 25565);
+    ///     let (basic, full) = server.query_all()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_all(&self) -> Result<(QueryBasic, QueryFull), MspErr> {
+        retry(&self.socket_conf, || query::query_all_status(self))
+    }
+
+    /// Send only the handshake of the [Query](https://wiki.vg/Query) protocol, returning
+    /// the challenge token for [Conf::query_basic_with_token] and [Conf::query_full_with_token].
+    ///
+    /// Useful for polling loops, as a token can be reused for several stat requests
+    /// until the server expires it, after 30 seconds for vanilla servers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create_with_port("www.example.com", 25565);
+    ///     let token = server.query_handshake()?;
+    ///     let basic = server.query_basic_with_token(&token)?;
+    ///     let full = server.query_full_with_token(&token)?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_handshake(&self) -> Result<QueryToken, MspErr> {
+        retry(&self.socket_conf, || query::query_handshake(self))
+    }
+
+    /// Same as [Conf::query], with the challenge token of a previous [Conf::query_handshake].
+    ///
+    /// The stat request is sent from [QueryToken::local_addr] to [QueryToken::server_addr],
+    /// only the timeouts and retries of this configuration apply.
+    pub fn query_basic_with_token(&self, token: &QueryToken) -> Result<QueryBasic, MspErr> {
+        retry(&self.socket_conf, || {
+            query::query_basic_status_with_token(self, token)
+        })
+    }
+
+    /// Same as [Conf::query_full], with the challenge token of a previous [Conf::query_handshake],
+    /// see [Conf::query_basic_with_token].
+    pub fn query_full_with_token(&self, token: &QueryToken) -> Result<QueryFull, MspErr> {
+        retry(&self.socket_conf, || {
+            query::query_full_status_with_token(self, token)
+        })
     }
 
     /// Get info from a modern Bedrock Edition servers using the [RakNet](https://wiki.vg/Raknet_Protocol) protocol
@@ -279,13 +1069,628 @@ impl Conf {
     /// use msp::{Conf, MspErr};
     ///
     /// fn main() -> Result<(), MspErr> {
-    ///     let server = Conf::create_with_port("www.example.com", 19132);
+    ///     let server = Conf::create_bedrock("www.example.com");
     ///     let info = server.get_bedrock_server_status()?;
     ///
     ///     Ok(())
     /// }
     /// ```
     pub fn get_bedrock_server_status(&self) -> Result<BedrockServer, MspErr> {
-        server::get_bedrock_server_status(self)
+        retry(&self.socket_conf, || {
+            server::get_bedrock_server_status(self)
+        })
+    }
+
+    /// Get info from a server of unknown kind, by probing each protocol in order.
+    ///
+    /// Tries [Conf::get_server_status], [Conf::get_netty_server_status],
+    /// [Conf::get_legacy_server_status] and [Conf::get_beta_legacy_server_status] in turn,
+    /// then [Conf::get_bedrock_server_status] (first, if the port is 19132). A protocol is
+    /// skipped only if the server does not understand it. Genuine network failures, such as
+    /// an unresolvable host, are returned immediately.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr, ServerKind};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///
+    ///     match server.get_status_auto()? {
+    ///         ServerKind::Regular(info) => println!("{}", info),
+    ///         other => println!("{}", other),
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_status_auto(&self) -> Result<ServerKind, MspErr> {
+        retry(&self.socket_conf, || server::get_status_auto(self))
+    }
+
+    /// Find out which protocols the server answers to, for picking the right method
+    /// for an unknown server.
+    ///
+    /// Every protocol of [ProtocolSupport] is tried once on [Conf::port], with each socket
+    /// timeout capped at 2 seconds and no retries. Bedrock Edition and Query usually listen
+    /// on another port than Java Edition, so probe that port separately if needed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::Conf;
+    ///
+    /// let support = Conf::create("www.example.com").probe_protocols();
+    ///
+    /// if !support.modern && support.legacy {
+    ///     println!("Use get_legacy_server_status");
+    /// }
+    /// ```
+    pub fn probe_protocols(&self) -> ProtocolSupport {
+        server::probe_protocols(self)
+    }
+
+    /// Same as [Conf::get_server_status], but over the given stream instead of
+    /// a TCP connection to [Conf::host], see [PacketStream].
+    ///
+    /// The stream must already be connected to the server, no retry is done and
+    /// [Server::addr] is left empty.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    /// use std::net::TcpStream;
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     // Any `Read + Write` stream, e.g. a Unix domain socket, works the same.
+    ///     let mut stream = TcpStream::connect("127.0.0.1:25565")?;
+    ///     let info = Conf::create("localhost").get_server_status_over(&mut stream)?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_server_status_over<S: PacketStream>(
+        &self,
+        stream: &mut S,
+    ) -> Result<Server, MspErr> {
+        server::get_server_status_over(stream, self)
+    }
+
+    /// Same as [Conf::get_netty_server_status], over the given stream, see [Conf::get_server_status_over].
+    pub fn get_netty_server_status_over<S: PacketStream>(
+        &self,
+        stream: &mut S,
+    ) -> Result<NettyServer, MspErr> {
+        server::get_netty_server_status_over(stream, self)
+    }
+
+    /// Same as [Conf::get_legacy_server_status], over the given stream, see [Conf::get_server_status_over].
+    pub fn get_legacy_server_status_over<S: PacketStream>(
+        &self,
+        stream: &mut S,
+    ) -> Result<LegacyServer, MspErr> {
+        server::get_legacy_server_status_over(stream)
+    }
+
+    /// Same as [Conf::get_beta_legacy_server_status], over the given stream, see [Conf::get_server_status_over].
+    pub fn get_beta_legacy_server_status_over<S: PacketStream>(
+        &self,
+        stream: &mut S,
+    ) -> Result<LegacyBetaServer, MspErr> {
+        server::get_beta_legacy_server_status_over(stream)
+    }
+
+    /// Async version of [Conf::get_server_status], requires the `tokio` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// async fn example() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let info = server.get_server_status_async().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn get_server_status_async(&self) -> Result<Server, MspErr> {
+        retry_async(&self.socket_conf, || server::get_server_status_async(self)).await
+    }
+
+    /// Async version of [Conf::get_status_auto], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_status_auto_async(&self) -> Result<ServerKind, MspErr> {
+        retry_async(&self.socket_conf, || server::get_status_auto_async(self)).await
+    }
+
+    /// Async version of [Conf::probe_protocols], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn probe_protocols_async(&self) -> ProtocolSupport {
+        server::probe_protocols_async(self).await
+    }
+
+    /// Async version of [Conf::get_server_status_raw], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_server_status_raw_async(&self) -> Result<serde_json::Value, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_server_status_raw_async(self)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::get_server_status_bytes], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_server_status_bytes_async(&self) -> Result<Vec<u8>, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_server_status_bytes_async(self)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::get_player_counts], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_player_counts_async(&self) -> Result<(i32, i32), MspErr> {
+        retry_async(&self.socket_conf, || server::get_player_counts_async(self)).await
+    }
+
+    /// Async version of [Conf::snapshot], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn snapshot_async(&self) -> ServerSnapshot {
+        ServerSnapshot::create(
+            self.get_server_status_async().await,
+            self.socket_conf.measure_ping,
+        )
+    }
+
+    /// Async version of [Conf::is_online], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn is_online_async(&self) -> bool {
+        retry_async(&self.socket_conf, || {
+            server::check_server_online_async(self)
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Async version of [Conf::ping], requires the `tokio` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// async fn example() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let latency = server.ping_async().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn ping_async(&self) -> Result<Duration, MspErr> {
+        retry_async(&self.socket_conf, || server::get_server_latency_async(self)).await
+    }
+
+    /// Async version of [Conf::get_netty_server_status], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_netty_server_status_async(&self) -> Result<NettyServer, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_netty_server_status_async(self)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::get_legacy_server_status], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_legacy_server_status_async(&self) -> Result<LegacyServer, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_legacy_server_status_async(self)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::get_beta_legacy_server_status], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_beta_legacy_server_status_async(&self) -> Result<LegacyBetaServer, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_beta_legacy_server_status_async(self)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::query], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_async(&self) -> Result<QueryBasic, MspErr> {
+        retry_async(&self.socket_conf, || query::query_basic_status_async(self)).await
+    }
+
+    /// Async version of [Conf::query_full], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_full_async(&self) -> Result<QueryFull, MspErr> {
+        retry_async(&self.socket_conf, || query::query_full_status_async(self)).await
+    }
+
+    /// Async version of [Conf::query_players], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_players_async(&self) -> Result<Vec<String>, MspErr> {
+        retry_async(&self.socket_conf, || query::query_players_async(self)).await
+    }
+
+    /// Async version of [Conf::query_all], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_all_async(&self) -> Result<(QueryBasic, QueryFull), MspErr> {
+        retry_async(&self.socket_conf, || query::query_all_status_async(self)).await
+    }
+
+    /// Async version of [Conf::query_handshake], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_handshake_async(&self) -> Result<QueryToken, MspErr> {
+        retry_async(&self.socket_conf, || query::query_handshake_async(self)).await
+    }
+
+    /// Async version of [Conf::query_basic_with_token], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_basic_with_token_async(
+        &self,
+        token: &QueryToken,
+    ) -> Result<QueryBasic, MspErr> {
+        retry_async(&self.socket_conf, || {
+            query::query_basic_status_with_token_async(self, token)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::query_full_with_token], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_full_with_token_async(
+        &self,
+        token: &QueryToken,
+    ) -> Result<QueryFull, MspErr> {
+        retry_async(&self.socket_conf, || {
+            query::query_full_status_with_token_async(self, token)
+        })
+        .await
+    }
+
+    /// Async version of [Conf::get_bedrock_server_status], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_bedrock_server_status_async(&self) -> Result<BedrockServer, MspErr> {
+        retry_async(&self.socket_conf, || {
+            server::get_bedrock_server_status_async(self)
+        })
+        .await
+    }
+
+    /// Same as [Conf::get_server_status], along with the resolved address and elapsed time.
+    ///
+    /// The host is resolved once beforehand and only the first usable address is tried,
+    /// so that [StatusResult::resolved_addr] is the one actually connected to.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use msp::{Conf, MspErr};
+    ///
+    /// fn main() -> Result<(), MspErr> {
+    ///     let server = Conf::create("www.example.com");
+    ///     let result = server.get_server_status_with_meta()?;
+    ///
+    ///     println!("{} took {:?}", result.resolved_addr, result.elapsed);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_server_status_with_meta(&self) -> Result<StatusResult<Server>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(self.socket_conf.ip_preference)?;
+
+        Ok(StatusResult::create(
+            conf.get_server_status()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Same as [Conf::get_netty_server_status], see [Conf::get_server_status_with_meta].
+    pub fn get_netty_server_status_with_meta(&self) -> Result<StatusResult<NettyServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(self.socket_conf.ip_preference)?;
+
+        Ok(StatusResult::create(
+            conf.get_netty_server_status()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Same as [Conf::get_legacy_server_status], see [Conf::get_server_status_with_meta].
+    pub fn get_legacy_server_status_with_meta(&self) -> Result<StatusResult<LegacyServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(self.socket_conf.ip_preference)?;
+
+        Ok(StatusResult::create(
+            conf.get_legacy_server_status()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Same as [Conf::get_beta_legacy_server_status], see [Conf::get_server_status_with_meta].
+    pub fn get_beta_legacy_server_status_with_meta(
+        &self,
+    ) -> Result<StatusResult<LegacyBetaServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(self.socket_conf.ip_preference)?;
+
+        Ok(StatusResult::create(
+            conf.get_beta_legacy_server_status()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Same as [Conf::query], see [Conf::get_server_status_with_meta].
+    pub fn query_with_meta(&self) -> Result<StatusResult<QueryBasic>, MspErr> {
+        let start = Instant::now();
+        // UDP requests prefer IPv4, see [SocketConf::rep_udp_ipv6].
+        let (conf, resolved_addr) = self.pin_resolved_addr(IpPreference::Ipv4)?;
+
+        Ok(StatusResult::create(conf.query()?, resolved_addr, start))
+    }
+
+    /// Same as [Conf::query_full], see [Conf::get_server_status_with_meta].
+    pub fn query_full_with_meta(&self) -> Result<StatusResult<QueryFull>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(IpPreference::Ipv4)?;
+
+        Ok(StatusResult::create(
+            conf.query_full()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Same as [Conf::get_bedrock_server_status], see [Conf::get_server_status_with_meta].
+    pub fn get_bedrock_server_status_with_meta(
+        &self,
+    ) -> Result<StatusResult<BedrockServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr(IpPreference::Ipv4)?;
+
+        Ok(StatusResult::create(
+            conf.get_bedrock_server_status()?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::get_server_status_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_server_status_with_meta_async(&self) -> Result<StatusResult<Server>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self
+            .pin_resolved_addr_async(self.socket_conf.ip_preference)
+            .await?;
+
+        Ok(StatusResult::create(
+            conf.get_server_status_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::get_netty_server_status_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_netty_server_status_with_meta_async(
+        &self,
+    ) -> Result<StatusResult<NettyServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self
+            .pin_resolved_addr_async(self.socket_conf.ip_preference)
+            .await?;
+
+        Ok(StatusResult::create(
+            conf.get_netty_server_status_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::get_legacy_server_status_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_legacy_server_status_with_meta_async(
+        &self,
+    ) -> Result<StatusResult<LegacyServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self
+            .pin_resolved_addr_async(self.socket_conf.ip_preference)
+            .await?;
+
+        Ok(StatusResult::create(
+            conf.get_legacy_server_status_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::get_beta_legacy_server_status_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_beta_legacy_server_status_with_meta_async(
+        &self,
+    ) -> Result<StatusResult<LegacyBetaServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self
+            .pin_resolved_addr_async(self.socket_conf.ip_preference)
+            .await?;
+
+        Ok(StatusResult::create(
+            conf.get_beta_legacy_server_status_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::query_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_with_meta_async(&self) -> Result<StatusResult<QueryBasic>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr_async(IpPreference::Ipv4).await?;
+
+        Ok(StatusResult::create(
+            conf.query_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::query_full_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn query_full_with_meta_async(&self) -> Result<StatusResult<QueryFull>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr_async(IpPreference::Ipv4).await?;
+
+        Ok(StatusResult::create(
+            conf.query_full_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Async version of [Conf::get_bedrock_server_status_with_meta], requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn get_bedrock_server_status_with_meta_async(
+        &self,
+    ) -> Result<StatusResult<BedrockServer>, MspErr> {
+        let start = Instant::now();
+        let (conf, resolved_addr) = self.pin_resolved_addr_async(IpPreference::Ipv4).await?;
+
+        Ok(StatusResult::create(
+            conf.get_bedrock_server_status_async().await?,
+            resolved_addr,
+            start,
+        ))
+    }
+
+    /// Resolve the connection target, which is the proxy when set,
+    /// and pin a copy of the conf to the first usable address.
+    fn pin_resolved_addr(&self, preference: IpPreference) -> Result<(Conf, SocketAddr), MspErr> {
+        let addrs = match &self.proxy {
+            Some(proxy) => resolve_host(self, &proxy.host, proxy.port, preference)?,
+            None => resolve_conf_addrs(self, preference)?,
+        };
+
+        Ok(self.pin_addr(&addrs))
+    }
+
+    /// Async version of [Conf::pin_resolved_addr].
+    #[cfg(feature = "tokio")]
+    async fn pin_resolved_addr_async(
+        &self,
+        preference: IpPreference,
+    ) -> Result<(Conf, SocketAddr), MspErr> {
+        let addrs = match &self.proxy {
+            Some(proxy) => resolve_host_async(self, &proxy.host, proxy.port, preference).await?,
+            None => resolve_conf_addrs_async(self, preference).await?,
+        };
+
+        Ok(self.pin_addr(&addrs))
+    }
+
+    /// Resolved addresses are never empty, see [resolve_addrs].
+    fn pin_addr(&self, addrs: &[SocketAddr]) -> (Conf, SocketAddr) {
+        let addr = addrs
+            .iter()
+            .find(|addr| is_same_family(self.socket_conf.bind_addr, addr))
+            .unwrap_or(&addrs[0]);
+        let mut conf = self.clone();
+
+        match &mut conf.proxy {
+            // The proxy resolves the server host itself.
+            Some(proxy) => proxy.host = addr.ip().to_string(),
+            None => {
+                // Keep the hostname for the handshake, servers may rely on it for virtual hosting.
+                conf.handshake_host = Some(
+                    self.handshake_host
+                        .clone()
+                        .unwrap_or_else(|| self.host.clone()),
+                );
+                conf.host = addr.ip().to_string();
+            }
+        }
+
+        (conf, *addr)
+    }
+}
+
+#[cfg(test)]
+mod conf_test {
+    use super::*;
+    use crate::share::create_tcp_socket;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_resolver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let conf = Conf::builder()
+            .host("mc.test")
+            .port(addr.port())
+            .resolver(Resolver::new(|host, port| match host {
+                "mc.test" => Ok(vec![([127, 0, 0, 1], port).into()]),
+                _ => Ok(vec![]),
+            }))
+            .build();
+
+        assert_eq!(conf.to_socket_addrs().unwrap().collect::<Vec<_>>(), [addr]);
+        assert_eq!(create_tcp_socket(&conf).unwrap().peer_addr().unwrap(), addr);
+        assert!(matches!(
+            create_tcp_socket(&Conf {
+                host: "other.test".into(),
+                ..conf
+            }),
+            Err(MspErr::DnsErr(_))
+        ));
+    }
+
+    #[test]
+    fn test_pin_addr() {
+        let conf = Conf::create_with_port("mc.example.com", 25566);
+        let addrs = [
+            "[::1]:25566".parse().unwrap(),
+            "127.0.0.1:25566".parse().unwrap(),
+        ];
+        let (pinned, addr) = conf.pin_addr(&addrs);
+
+        assert_eq!(addr, addrs[0]);
+        assert_eq!(pinned.host, "::1");
+        assert_eq!(pinned.handshake_host.as_deref(), Some("mc.example.com"));
+
+        let mut conf = conf;
+
+        conf.socket_conf.bind_addr = Some("0.0.0.0:0".parse().unwrap());
+        conf.proxy = Some(ProxyConf {
+            host: "proxy.example.com".into(),
+            port: 1080,
+            credentials: None,
+        });
+
+        let (pinned, addr) = conf.pin_addr(&addrs);
+
+        assert_eq!(addr, addrs[1]);
+        assert_eq!(pinned.host, "mc.example.com");
+        assert_eq!(pinned.proxy.unwrap().host, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_snapshot_of_error() {
+        let snapshot = ServerSnapshot::create(Err(MspErr::DataErr("Bad status".into())), true);
+
+        assert!(!snapshot.online);
+        assert_eq!(snapshot.latency, None);
+        assert_eq!(snapshot.error.as_deref(), Some("Bad status"));
     }
 }
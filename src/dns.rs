@@ -0,0 +1,363 @@
+use crate::{share::send_datagram, MspErr, SocketConf};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    time::SystemTime,
+};
+
+const DNS_PORT: u16 = 53;
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+/// [SRV](https://datatracker.ietf.org/doc/html/rfc2782) record type.
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// Response code for a domain name that does not exist.
+const RCODE_NXDOMAIN: u8 = 3;
+/// Plain DNS over UDP is limited to 512 bytes.
+const MAX_UDP_MESSAGE_SIZE: usize = 512;
+/// TC bit of the header flags, set when the answer did not fit in the UDP message.
+const FLAG_TRUNCATED: u8 = 0x02;
+
+/// A resolved [SRV](https://datatracker.ietf.org/doc/html/rfc2782) record.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Look up the SRV record of `name` using the nameservers from the system
+/// resolver configuration, in order until one answers.
+///
+/// Return `None` when the domain has no SRV record or the record explicitly
+/// declares that the service is not available (target is `.`).
+pub fn resolve_srv_record(
+    name: &str,
+    socket_conf: &SocketConf,
+) -> Result<Option<SrvRecord>, MspErr> {
+    let mut last_err = None;
+
+    for nameserver in get_system_nameservers()? {
+        match query_srv_record(nameserver, name, socket_conf) {
+            Ok(record) => return Ok(record),
+            Err(err) => {
+                log::debug!("Nameserver {} failed: {}", nameserver, err);
+                last_err = Some(err);
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| MspErr::DnsErr("No nameserver to query.".into())))
+}
+
+/// Query a single nameserver over UDP, retrying over TCP if the answer is truncated.
+fn query_srv_record(
+    nameserver: IpAddr,
+    name: &str,
+    socket_conf: &SocketConf,
+) -> Result<Option<SrvRecord>, MspErr> {
+    let socket = UdpSocket::bind(match nameserver {
+        IpAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        IpAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    })?;
+    let id = random_query_id();
+    let packet = build_srv_query_packet(id, name)?;
+    let mut bufs = [0u8; MAX_UDP_MESSAGE_SIZE];
+
+    socket.set_read_timeout(socket_conf.read_time_out)?;
+    socket.set_write_timeout(socket_conf.write_timeout)?;
+    socket.connect((nameserver, DNS_PORT))?;
+    send_datagram(&socket, &packet)?;
+
+    let len = socket.recv(&mut bufs)?;
+
+    match is_truncated(&bufs[..len]) {
+        true => {
+            log::debug!(
+                "Truncated SRV answer from {}, retrying over TCP.",
+                nameserver
+            );
+
+            process_srv_response(id, &query_over_tcp(nameserver, &packet, socket_conf)?)
+        }
+        false => process_srv_response(id, &bufs[..len]),
+    }
+}
+
+/// Send the query over TCP, where messages are prefixed with their 2-byte length.
+fn query_over_tcp(
+    nameserver: IpAddr,
+    packet: &[u8],
+    socket_conf: &SocketConf,
+) -> Result<Vec<u8>, MspErr> {
+    let addr = SocketAddr::from((nameserver, DNS_PORT));
+    let mut stream = match socket_conf.connect_timeout.or(socket_conf.read_time_out) {
+        Some(timeout) => TcpStream::connect_timeout(&addr, timeout)?,
+        None => TcpStream::connect(addr)?,
+    };
+    let mut request = (packet.len() as u16).to_be_bytes().to_vec();
+    let mut len = [0u8; 2];
+
+    request.extend_from_slice(packet);
+    stream.set_read_timeout(socket_conf.read_time_out)?;
+    stream.set_write_timeout(socket_conf.write_timeout)?;
+    stream.write_all(&request)?;
+    stream.read_exact(&mut len)?;
+
+    let mut bufs = vec![0u8; u16::from_be_bytes(len) as usize];
+
+    stream.read_exact(&mut bufs)?;
+
+    Ok(bufs)
+}
+
+fn is_truncated(bufs: &[u8]) -> bool {
+    bufs.get(2).is_some_and(|flags| flags & FLAG_TRUNCATED != 0)
+}
+
+/// Unpredictable query id, so that an off-path attacker cannot spoof the answer.
+fn random_query_id() -> u16 {
+    // Each `RandomState` is seeded with random keys.
+    let mut hasher = RandomState::new().build_hasher();
+
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos()),
+    );
+    hasher.finish() as u16
+}
+
+/// Get the nameservers from `/etc/resolv.conf`, in the order they are listed.
+fn get_system_nameservers() -> Result<Vec<IpAddr>, MspErr> {
+    let resolv_conf = std::fs::read_to_string(RESOLV_CONF_PATH).map_err(|err| {
+        MspErr::NoImpl(format!(
+            "Cannot read system resolver configuration({}): {}",
+            RESOLV_CONF_PATH, err
+        ))
+    })?;
+    let nameservers = resolv_conf
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        // Drop the IPv6 zone index (e.g. fe80::1%eth0), it cannot be parsed.
+        .filter_map(|addr| addr.trim().split('%').next()?.parse::<IpAddr>().ok())
+        .collect::<Vec<_>>();
+
+    match nameservers.is_empty() {
+        true => Err(MspErr::NoImpl(format!(
+            "No nameserver found in {}",
+            RESOLV_CONF_PATH
+        ))),
+        false => Ok(nameservers),
+    }
+}
+
+/// Build a recursive DNS query packet asking for the SRV record of `name`.
+fn build_srv_query_packet(id: u16, name: &str) -> Result<Vec<u8>, MspErr> {
+    let mut packet = Vec::<u8>::new();
+
+    // Header: ID, flags(recursion desired), QDCOUNT = 1, ANCOUNT, NSCOUNT, ARCOUNT = 0
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    // Question: QNAME as a sequence of length-prefixed labels.
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(MspErr::DataErr(format!("Invalid domain name: {}", name)));
+        }
+
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+
+    packet.push(0x00);
+    packet.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(packet)
+}
+
+/// Parse the DNS response and pick the SRV record with the lowest priority
+/// (and the highest weight among the same priority).
+fn process_srv_response(id: u16, bufs: &[u8]) -> Result<Option<SrvRecord>, MspErr> {
+    if bufs.len() < 12 {
+        return Err(MspErr::DataErr(format!(
+            "DNS response is too short, expected at least 12 bytes, but got: {}",
+            bufs.len()
+        )));
+    }
+
+    if u16::from_be_bytes([bufs[0], bufs[1]]) != id {
+        return Err(MspErr::DataErr("DNS response id mismatch.".into()));
+    }
+
+    if bufs[2] & 0x80 == 0 {
+        return Err(MspErr::DataErr("DNS packet is not a response.".into()));
+    }
+
+    // Records past the end are cut off, so the answer cannot be trusted to be complete.
+    if is_truncated(bufs) {
+        return Err(MspErr::DataErr(
+            "DNS response is truncated and cannot be read whole.".into(),
+        ));
+    }
+
+    match bufs[3] & 0x0F {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(None),
+        rcode => {
+            return Err(MspErr::DataErr(format!(
+                "DNS server responded with error code: {}",
+                rcode
+            )));
+        }
+    }
+
+    let qd_count = u16::from_be_bytes([bufs[4], bufs[5]]);
+    let an_count = u16::from_be_bytes([bufs[6], bufs[7]]);
+    let mut idx = 12;
+    let mut records = Vec::<SrvRecord>::new();
+
+    for _ in 0..qd_count {
+        idx = skip_name(bufs, idx)? + 4;
+    }
+
+    for _ in 0..an_count {
+        idx = skip_name(bufs, idx)?;
+
+        let header = read_slice(bufs, idx, 10)?;
+        let r_type = u16::from_be_bytes([header[0], header[1]]);
+        let rd_len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let rdata_idx = idx + 10;
+
+        idx = rdata_idx + rd_len;
+
+        // Answers may also contain CNAME records, only SRV is interested.
+        if r_type != TYPE_SRV {
+            continue;
+        }
+
+        let rdata = read_slice(bufs, rdata_idx, 6)?;
+
+        records.push(SrvRecord {
+            priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+            weight: u16::from_be_bytes([rdata[2], rdata[3]]),
+            port: u16::from_be_bytes([rdata[4], rdata[5]]),
+            target: read_name(bufs, rdata_idx + 6)?,
+        });
+    }
+
+    Ok(records
+        .into_iter()
+        .min_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)))
+        .filter(|record| !record.target.is_empty()))
+}
+
+fn read_slice(bufs: &[u8], idx: usize, len: usize) -> Result<&[u8], MspErr> {
+    bufs.get(idx..idx + len)
+        .ok_or_else(|| MspErr::DataErr("Incomplete DNS response.".into()))
+}
+
+/// Skip a (possibly compressed) domain name and return the index after it.
+fn skip_name(bufs: &[u8], mut idx: usize) -> Result<usize, MspErr> {
+    loop {
+        match read_slice(bufs, idx, 1)?[0] {
+            0x00 => return Ok(idx + 1),
+            // Compression pointer takes 2 bytes and ends the name.
+            len if len & 0xC0 == 0xC0 => return Ok(idx + 2),
+            len => idx += 1 + len as usize,
+        }
+    }
+}
+
+/// Read a (possibly compressed) domain name, without the trailing dot.
+fn read_name(bufs: &[u8], mut idx: usize) -> Result<String, MspErr> {
+    let mut labels = Vec::<String>::new();
+    // Guard against pointer loops in malformed packets.
+    let mut jumps = 0;
+
+    loop {
+        match read_slice(bufs, idx, 1)?[0] {
+            0x00 => break,
+            len if len & 0xC0 == 0xC0 => {
+                let pointer = read_slice(bufs, idx, 2)?;
+
+                jumps += 1;
+
+                if jumps > MAX_UDP_MESSAGE_SIZE {
+                    return Err(MspErr::DataErr("DNS name compression loop.".into()));
+                }
+
+                idx = (u16::from_be_bytes([pointer[0] & 0x3F, pointer[1]])) as usize;
+            }
+            len => {
+                labels
+                    .push(String::from_utf8_lossy(read_slice(bufs, idx + 1, len as usize)?).into());
+                idx += 1 + len as usize;
+            }
+        }
+    }
+
+    Ok(labels.join("."))
+}
+
+#[cfg(test)]
+mod dns_test {
+    use super::*;
+
+    #[test]
+    fn test_process_srv_response() {
+        let mut response = build_srv_query_packet(0x1234, "_minecraft._tcp.example.com").unwrap();
+
+        // Mark as response with 2 answers.
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = 0x02;
+
+        for (priority, weight, port, target) in [
+            (10u16, 5u16, 25566u16, &b"\x02mc\x07example\x03com\x00"[..]),
+            // Lower priority wins. Target is compressed, pointing to "example.com" in question.
+            (5, 0, 25567, &b"\x04play\xC0\x1C"[..]),
+        ] {
+            // Name pointer to question, type SRV, class IN, TTL
+            response.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x21, 0x00, 0x01, 0, 0, 0x0E, 0x10]);
+            response.extend_from_slice(&((6 + target.len()) as u16).to_be_bytes());
+            response.extend_from_slice(&priority.to_be_bytes());
+            response.extend_from_slice(&weight.to_be_bytes());
+            response.extend_from_slice(&port.to_be_bytes());
+            response.extend_from_slice(target);
+        }
+
+        assert_eq!(
+            process_srv_response(0x1234, &response).unwrap(),
+            Some(SrvRecord {
+                priority: 5,
+                weight: 0,
+                port: 25567,
+                target: "play.example.com".into(),
+            })
+        );
+        assert!(process_srv_response(0x4321, &response).is_err());
+
+        // NXDOMAIN
+        response[3] = 0x83;
+        assert_eq!(process_srv_response(0x1234, &response).unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_srv_response() {
+        let mut response = build_srv_query_packet(0x1234, "_minecraft._tcp.example.com").unwrap();
+
+        response[2] = 0x83;
+        response[3] = 0x80;
+
+        assert!(is_truncated(&response));
+        assert!(matches!(
+            process_srv_response(0x1234, &response),
+            Err(MspErr::DataErr(_))
+        ));
+        assert!(!is_truncated(&[0x12, 0x34]));
+    }
+}
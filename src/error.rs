@@ -7,8 +7,17 @@ pub enum MspErr {
     InternalErr(String),
     /// Unimplemented features.
     NoImpl(String),
+    /// The server actively refused the connection, usually because it is offline.
+    ConnRefused(std::io::Error),
+    /// A socket operation or the whole exchange timed out, e.g. the server is firewalled.
+    Timeout(std::io::Error),
+    /// The host name cannot be resolved.
+    DnsErr(String),
     /// Handling errors that occur during sockets.
     IoErr(std::io::Error),
+    /// The status response is not valid JSON, or does not match the expected structure.
+    /// The wrapped error gives the line and column.
+    JsonErr(serde_json::Error),
 }
 
 impl std::fmt::Display for MspErr {
@@ -17,16 +26,60 @@ impl std::fmt::Display for MspErr {
             MspErr::DataErr(str) => write!(f, "{}", str),
             MspErr::InternalErr(str) => write!(f, "{}", str),
             MspErr::NoImpl(str) => write!(f, "{}", str),
+            MspErr::ConnRefused(err) => write!(f, "{}", err),
+            MspErr::Timeout(err) => write!(f, "{}", err),
+            MspErr::DnsErr(str) => write!(f, "{}", str),
             MspErr::IoErr(err) => write!(f, "{}", err),
+            MspErr::JsonErr(err) => write!(f, "{}", err),
         }
     }
 }
 
-impl std::error::Error for MspErr {}
+/// The wrapped [std::io::Error] and [serde_json::Error] cannot be cloned, so the clone
+/// only keeps their kind and message, e.g. to share a [Server::errors](crate::Server::errors).
+impl Clone for MspErr {
+    fn clone(&self) -> Self {
+        let io_err = |err: &std::io::Error| std::io::Error::new(err.kind(), err.to_string());
+
+        match self {
+            MspErr::DataErr(str) => MspErr::DataErr(str.clone()),
+            MspErr::InternalErr(str) => MspErr::InternalErr(str.clone()),
+            MspErr::NoImpl(str) => MspErr::NoImpl(str.clone()),
+            MspErr::ConnRefused(err) => MspErr::ConnRefused(io_err(err)),
+            MspErr::Timeout(err) => MspErr::Timeout(io_err(err)),
+            MspErr::DnsErr(str) => MspErr::DnsErr(str.clone()),
+            MspErr::IoErr(err) => MspErr::IoErr(io_err(err)),
+            MspErr::JsonErr(err) => {
+                MspErr::JsonErr(<serde_json::Error as serde::de::Error>::custom(err))
+            }
+        }
+    }
+}
+
+impl std::error::Error for MspErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MspErr::ConnRefused(err) | MspErr::Timeout(err) | MspErr::IoErr(err) => Some(err),
+            MspErr::JsonErr(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 impl From<std::io::Error> for MspErr {
     fn from(err: std::io::Error) -> Self {
-        MspErr::IoErr(err)
+        match err.kind() {
+            std::io::ErrorKind::ConnectionRefused => MspErr::ConnRefused(err),
+            // Read timeout is reported as `WouldBlock` on Unix.
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => MspErr::Timeout(err),
+            _ => MspErr::IoErr(err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for MspErr {
+    fn from(err: serde_json::Error) -> Self {
+        MspErr::JsonErr(err)
     }
 }
 
@@ -1,19 +1,32 @@
-use crate::{share::create_udp_socket, MspErr, SocketConf};
+use crate::{
+    server::{
+        build_unconnected_ping_packet, process_unconnected_pong, BedrockServer, UNCONNECTED_PONG_ID,
+    },
+    share::{check_datagram_sent, create_udp_socket, display_json, ToJson, UDP_MAX_DATAGRAM_SIZE},
+    MspErr, SocketConf, BEDROCK_DEFAULT_PORT,
+};
 use serde::Serialize;
 use std::{
-    net::{Ipv4Addr, SocketAddrV4},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     sync::mpsc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 const MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 2, 60);
+const MULTICAST_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xFF75, 0x0230, 0, 0, 0, 0, 0, 0x0060);
 const MULTICAST_PORT: u16 = 4445;
+/// How often [get_bedrock_lan_server_status] broadcasts an unconnected ping.
+const BEDROCK_LAN_PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Read timeout used by [get_lan_server_status_for] if none is configured.
+const LAN_POLL_INTERVAL: Duration = Duration::from_millis(500);
 const BROADCAST_MUST_CONTAIN: [&'static str; 4] = ["[MOTD]", "[/MOTD]", "[AD]", "[/AD]"];
 
 /// LAN server info structure.
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct LanServer {
-    /// SocketAddrV4 information for the target server from `recv_from`.
-    pub addr: SocketAddrV4,
+    /// SocketAddr information for the target server from `recv_from`.
+    pub addr: SocketAddr,
     /// MOTD of the target server.
     pub motd: String,
     /// Open port of the target server.
@@ -21,7 +34,7 @@ pub struct LanServer {
 }
 
 impl LanServer {
-    fn create(addr: SocketAddrV4, motd: String, port: u16) -> Self {
+    fn create(addr: SocketAddr, motd: String, port: u16) -> Self {
         Self { addr, motd, port }
     }
 }
@@ -34,18 +47,119 @@ impl std::hash::Hash for LanServer {
 
 impl std::fmt::Display for LanServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
+    }
+}
+
+impl ToJson for LanServer {}
+
+/// Bedrock Edition LAN server info structure.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BedrockLanServer {
+    /// SocketAddr information for the target server from `recv_from`.
+    pub addr: SocketAddr,
+    /// Server info from the unconnected pong.
+    pub server: BedrockServer,
+}
+
+impl std::fmt::Display for BedrockLanServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_json(self, f)
+    }
+}
+
+impl ToJson for BedrockLanServer {}
+
+/// Discovery handle and the discovered servers.
+type Discovery<T> = (LanDiscovery, LanServers<T>);
+
+/// Servers discovered by [get_lan_server_status] or [get_bedrock_lan_server_status].
+///
+/// Iterating blocks until the next server is discovered, and ends once the discovery stopped.
+/// The discovery threads also send `Ok(None)` whenever [SocketConf::read_time_out] passes
+/// without a broadcast, which the iterator skips. Use [LanServers::receiver] to get them,
+/// e.g. to do some work periodically, or disable them with [SocketConf::lan_heartbeat].
+pub struct LanServers<T> {
+    rx: mpsc::Receiver<Result<Option<T>, MspErr>>,
+}
+
+impl<T> LanServers<T> {
+    /// The underlying channel, including the `Ok(None)` sent on read timeout.
+    pub fn receiver(&self) -> &mpsc::Receiver<Result<Option<T>, MspErr>> {
+        &self.rx
+    }
+
+    /// Take the underlying channel, see [LanServers::receiver].
+    pub fn into_receiver(self) -> mpsc::Receiver<Result<Option<T>, MspErr>> {
+        self.rx
+    }
+}
+
+impl<T> Iterator for LanServers<T> {
+    type Item = Result<T, MspErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.rx.recv() {
+                Ok(Ok(Some(server))) => return Some(Ok(server)),
+                Ok(Ok(None)) => {}
+                Ok(Err(err)) => return Some(Err(err)),
+                // All discovery threads stopped.
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// Handle of a running LAN discovery, returned by [get_lan_server_status]
+/// and [get_bedrock_lan_server_status].
+///
+/// Dropping the handle does not stop the discovery, call [LanDiscovery::stop] for that.
+pub struct LanDiscovery {
+    t_senders: Vec<mpsc::Sender<()>>,
+    threads: Vec<JoinHandle<Result<(), MspErr>>>,
+}
+
+impl LanDiscovery {
+    /// Ask the discovery threads to leave the multicast group and stop.
+    ///
+    /// The threads notice the request after the current read returns, which takes up to
+    /// [SocketConf::read_time_out]. Threads that already stopped, e.g. because of an error
+    /// or because the receiver was dropped, are skipped.
+    pub fn stop(&self) -> Result<(), MspErr> {
+        for t_sender in &self.t_senders {
+            // The receiving thread may have already stopped.
+            let _ = t_sender.send(());
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the discovery threads to finish.
+    ///
+    /// Returns the first error that could not be delivered through the receiver, such as
+    /// failing to leave the multicast group.
+    pub fn join(self) -> Result<(), MspErr> {
+        let mut result = Ok(());
+
+        for thread in self.threads {
+            let thread_result = thread.join().unwrap_or_else(|_| {
+                Err(MspErr::InternalErr("LAN discovery thread panicked".into()))
+            });
+
+            result = result.and(thread_result);
+        }
+
+        result
     }
 }
 
 /// Get the host information of other open servers in the current LAN.
 ///
-/// Currently, it only prints the host information cyclically, and does not return [LanServer] information.
-/// # TODO Get host information for a period of time by passing in duration control.
+/// Discovered servers can be iterated from the returned [LanServers] until [LanDiscovery::stop]
+/// is called on the returned handle. To collect servers for a period of time, use [get_lan_server_status_for].
+///
+/// Set [SocketConf::lan_ipv6] to also receive broadcasts sent to the IPv6 multicast group.
 ///
 /// # Example
 ///
@@ -53,17 +167,22 @@ impl std::fmt::Display for LanServer {
 /// use msp::{get_lan_server_status, MspErr, SocketConf};
 ///
 /// fn main() -> Result<(), MspErr> {
-///    let info =  get_lan_server_status(&SocketConf::default())?;
+///     let (discovery, servers) = get_lan_server_status(&SocketConf::default())?;
 ///
-///     Ok(())
+///     for server in servers.take(3) {
+///         println!("{}", server?);
+///     }
+///
+///     discovery.stop()?;
+///     discovery.join()
 /// }
 /// ```
-pub fn get_lan_server_status(
-    socket_conf: &SocketConf,
-) -> Result<(impl Fn(), mpsc::Receiver<Result<Option<LanServer>, MspErr>>), MspErr> {
-    let mut buffer = [0u8; 256];
+pub fn get_lan_server_status(socket_conf: &SocketConf) -> Result<Discovery<LanServer>, MspErr> {
     let (tx, rx) = mpsc::channel::<Result<Option<LanServer>, MspErr>>();
-    let (t_sender, t_receiver) = mpsc::channel::<()>();
+    let mut discovery = LanDiscovery {
+        t_senders: Vec::new(),
+        threads: Vec::new(),
+    };
     let socket = create_udp_socket(&SocketConf {
         rep_udp_port: MULTICAST_PORT,
         ..socket_conf.clone()
@@ -71,115 +190,448 @@ pub fn get_lan_server_status(
 
     socket.join_multicast_v4(&MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED)?;
 
-    std::thread::spawn(move || {
-        let send_err = |err: MspErr| {
-            tx.send(Err(err))
-                .expect("An error occurred while sending an error message");
-        };
+    let mut sockets = vec![(socket, IpAddr::V4(MULTICAST_ADDR))];
 
-        'socket_receive_loop: loop {
-            match t_receiver.try_recv() {
-                Ok(_) | Err(mpsc::TryRecvError::Disconnected) => {
-                    socket
-                        .leave_multicast_v4(&MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED)
-                        .expect("An error occurred while leaving multicast");
+    if socket_conf.lan_ipv6 {
+        sockets.push((
+            create_udp_socket_v6(socket_conf)?,
+            IpAddr::V6(MULTICAST_ADDR_V6),
+        ));
+    }
 
-                    break;
-                }
-                Err(mpsc::TryRecvError::Empty) => {}
+    for (socket, group) in sockets {
+        let (t_sender, t_receiver) = mpsc::channel::<()>();
+        let tx = tx.clone();
+        let socket_conf = socket_conf.clone();
+
+        discovery.t_senders.push(t_sender);
+        discovery.threads.push(std::thread::spawn(move || {
+            receive_broadcast(socket, group, &socket_conf, tx, t_receiver)
+        }));
+    }
+
+    Ok((discovery, LanServers { rx }))
+}
+
+/// Get the host information of Bedrock Edition servers in the current LAN.
+///
+/// Unlike Java Edition, Bedrock Edition servers do not announce themselves. Like the game client,
+/// this broadcasts an unconnected ping to port 19132 every second and yields every unconnected pong
+/// from the returned [LanServers], until [LanDiscovery::stop] is called on the returned handle.
+/// A server is thus reported repeatedly, deduplicate by [BedrockServer::server_guid] if needed.
+///
+/// Only IPv4 is supported, the socket is created from [SocketConf::rep_udp_ipv4]
+/// and [SocketConf::rep_udp_port].
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{get_bedrock_lan_server_status, MspErr, SocketConf};
+///
+/// fn main() -> Result<(), MspErr> {
+///     let (discovery, mut servers) = get_bedrock_lan_server_status(&SocketConf::default())?;
+///
+///     if let Some(lan_server) = servers.next() {
+///         println!("{}", lan_server?);
+///     }
+///
+///     discovery.stop()?;
+///     discovery.join()
+/// }
+/// ```
+pub fn get_bedrock_lan_server_status(
+    socket_conf: &SocketConf,
+) -> Result<Discovery<BedrockLanServer>, MspErr> {
+    let (tx, rx) = mpsc::channel::<Result<Option<BedrockLanServer>, MspErr>>();
+    let (t_sender, t_receiver) = mpsc::channel::<()>();
+    let socket = create_udp_socket(socket_conf)?;
+
+    socket.set_broadcast(true)?;
+
+    let socket_conf = socket_conf.clone();
+    let thread =
+        std::thread::spawn(move || receive_bedrock_pong(socket, &socket_conf, tx, t_receiver));
+
+    Ok((
+        LanDiscovery {
+            t_senders: vec![t_sender],
+            threads: vec![thread],
+        },
+        LanServers { rx },
+    ))
+}
+
+/// Collect the servers in the current LAN for the given duration.
+///
+/// Servers are deduplicated by [LanServer::addr], keeping the latest broadcast message.
+/// Malformed broadcasts are skipped, only socket errors are returned.
+/// If [SocketConf::read_time_out] is not set, a short timeout is used so that the
+/// multicast subscription can be left right after the duration ends.
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{get_lan_server_status_for, MspErr, SocketConf};
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), MspErr> {
+///     let servers = get_lan_server_status_for(&SocketConf::default(), Duration::from_secs(3))?;
+///
+///     Ok(())
+/// }
+/// ```
+pub fn get_lan_server_status_for(
+    socket_conf: &SocketConf,
+    duration: Duration,
+) -> Result<Vec<LanServer>, MspErr> {
+    let deadline = Instant::now() + duration;
+    let (discovery, lan_servers) = get_lan_server_status(&SocketConf {
+        read_time_out: Some(socket_conf.read_time_out.unwrap_or(LAN_POLL_INTERVAL)),
+        ..socket_conf.clone()
+    })?;
+    let mut servers = Vec::<LanServer>::new();
+
+    let result = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        match lan_servers.receiver().recv_timeout(remaining) {
+            Ok(Ok(Some(server))) => match servers.iter_mut().find(|s| s.addr == server.addr) {
+                Some(exist_server) => *exist_server = server,
+                None => servers.push(server),
+            },
+            Ok(Ok(None)) => {}
+            // A malformed broadcast only concerns its sender, keep collecting.
+            Ok(Err(err @ (MspErr::DataErr(_) | MspErr::InternalErr(_)))) => {
+                log::debug!("Skip invalid LAN broadcast: {}", err);
             }
+            Ok(Err(err)) => break Err(err),
+            Err(_) => break Ok(servers),
+        }
+    };
 
-            let src_addr = match socket.recv_from(&mut buffer) {
-                Ok((_, addr)) => addr,
-                Err(err) => match err.kind() {
-                    std::io::ErrorKind::WouldBlock => {
-                        tx.send(Ok(None))
-                            .expect("An error occurred while sending an None message");
+    let stopped = discovery.stop().and_then(|_| discovery.join());
 
-                        continue 'socket_receive_loop;
-                    }
-                    _ => {
-                        send_err(MspErr::IoErr(err));
+    result.and_then(|servers| stopped.map(|_| servers))
+}
 
-                        break 'socket_receive_loop;
-                    }
-                },
-            };
-
-            let (motd, port) = match std::str::from_utf8(&buffer) {
-                Ok(str) => {
-                    // Check broadcast message is valid.
-                    //
-                    // If is not valid, it will continue outer loop immediately,
-                    // or throw an `MspErr` Error in strict mod(not impl):
-                    //
-                    // TODO Impl strict mod
-                    for str_must_contain in BROADCAST_MUST_CONTAIN {
-                        if !str.contains(str_must_contain) {
-                            continue 'socket_receive_loop;
-                        }
-                    }
+/// Create the socket listening to the IPv6 multicast group.
+fn create_udp_socket_v6(socket_conf: &SocketConf) -> Result<UdpSocket, MspErr> {
+    // On dual-stack systems other than Windows, `[::]` also covers IPv4 and conflicts
+    // with the IPv4 socket on the same port, so bind the group address instead.
+    let bind_addr = match cfg!(windows) {
+        true => Ipv6Addr::UNSPECIFIED,
+        false => MULTICAST_ADDR_V6,
+    };
+    let socket = UdpSocket::bind((bind_addr, MULTICAST_PORT))?;
+
+    socket.set_read_timeout(socket_conf.read_time_out)?;
+    socket.set_write_timeout(socket_conf.write_timeout)?;
+    socket.join_multicast_v6(&MULTICAST_ADDR_V6, 0)?;
+
+    Ok(socket)
+}
+
+/// Receive broadcast messages from the multicast `group` until terminated.
+///
+/// Errors are sent through `tx`, and only returned if the receiver is gone.
+fn receive_broadcast(
+    socket: UdpSocket,
+    group: IpAddr,
+    socket_conf: &SocketConf,
+    tx: mpsc::Sender<Result<Option<LanServer>, MspErr>>,
+    t_receiver: mpsc::Receiver<()>,
+) -> Result<(), MspErr> {
+    let mut buffer = vec![0u8; socket_conf.lan_buffer_size];
+    let send_err = |err: MspErr| {
+        tx.send(Err(err))
+            .or_else(|mpsc::SendError(msg)| msg.map(|_| ()))
+    };
 
-                    match abstract_broadcast_message(str) {
-                        Ok((m, p)) => (m, p),
-                        Err(err) => {
-                            send_err(err);
+    'socket_receive_loop: loop {
+        match t_receiver.try_recv() {
+            Ok(_) | Err(mpsc::TryRecvError::Disconnected) => break,
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
 
-                            return;
-                        }
+        let (len, src_addr) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) => match err.kind() {
+                // Read timeout is reported as `TimedOut` on Windows.
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                    // Nobody is listening anymore.
+                    if socket_conf.lan_heartbeat && tx.send(Ok(None)).is_err() {
+                        break;
                     }
-                }
-                Err(_) => {
-                    send_err(MspErr::InternalErr(format!(
-                        "invalid utf-8: corrupt contents: {:?}",
-                        buffer
-                    )));
 
-                    return;
+                    continue 'socket_receive_loop;
                 }
-            };
+                _ => return send_err(err.into()),
+            },
+        };
+
+        // The datagram may have been truncated, see [SocketConf::lan_buffer_size].
+        if len == buffer.len() {
+            log::debug!("Skip broadcast from {} filling the whole buffer.", src_addr);
 
-            match src_addr {
-                std::net::SocketAddr::V4(v4) => {
-                    tx.send(Ok(Some(LanServer::create(v4, motd.into(), port))))
-                        .expect("An error occurred while sending an LanServer message");
+            continue 'socket_receive_loop;
+        }
+
+        let result = match std::str::from_utf8(&buffer[..len]) {
+            Ok(str) => {
+                // Check broadcast message is valid.
+                //
+                // If is not valid, it will continue outer loop immediately,
+                // or throw an `MspErr` Error in strict mod(not impl):
+                //
+                // TODO Impl strict mod
+                for str_must_contain in BROADCAST_MUST_CONTAIN {
+                    if !str.contains(str_must_contain) {
+                        continue 'socket_receive_loop;
+                    }
                 }
-                std::net::SocketAddr::V6(_) => {
-                    tx.send(Err(MspErr::NoImpl(format!("Not impl for ipv6."))))
-                        .expect("An error occurred while sending an error message");
 
-                    return;
+                abstract_broadcast_message(str)
+                    .map(|(motd, port)| Some(LanServer::create(src_addr, motd.into(), port)))
+            }
+            Err(_) => Err(MspErr::DataErr(format!(
+                "Invalid UTF-8 broadcast from {}: {:?}",
+                src_addr,
+                &buffer[..len]
+            ))),
+        };
+
+        // A malformed broadcast only concerns its sender, keep listening.
+        if tx.send(result).is_err() {
+            break;
+        }
+    }
+
+    match group {
+        IpAddr::V4(group) => socket.leave_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(group) => socket.leave_multicast_v6(&group, 0),
+    }?;
+
+    Ok(())
+}
+
+/// Broadcast unconnected pings and receive the pongs until terminated.
+///
+/// Like [receive_broadcast], `Ok(None)` is sent whenever `read_time_out` passes without a pong,
+/// unless [SocketConf::lan_heartbeat] is false.
+fn receive_bedrock_pong(
+    socket: UdpSocket,
+    socket_conf: &SocketConf,
+    tx: mpsc::Sender<Result<Option<BedrockLanServer>, MspErr>>,
+    t_receiver: mpsc::Receiver<()>,
+) -> Result<(), MspErr> {
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+    let mut next_ping = Instant::now();
+    let mut last_received = Instant::now();
+    let send_err = |err: MspErr| {
+        tx.send(Err(err))
+            .or_else(|mpsc::SendError(msg)| msg.map(|_| ()))
+    };
+
+    loop {
+        match t_receiver.try_recv() {
+            Ok(_) | Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+
+        let now = Instant::now();
+
+        if now >= next_ping {
+            let ping = build_unconnected_ping_packet();
+            let sent = socket
+                .send_to(&ping, (Ipv4Addr::BROADCAST, BEDROCK_DEFAULT_PORT))
+                .map_err(MspErr::from)
+                .and_then(|sent| check_datagram_sent(sent, &ping));
+
+            if let Err(err) = sent {
+                return send_err(err);
+            }
+
+            next_ping = now + BEDROCK_LAN_PING_INTERVAL;
+        }
+
+        if let Some(read_time_out) = socket_conf
+            .read_time_out
+            .filter(|_| socket_conf.lan_heartbeat)
+        {
+            if now.duration_since(last_received) >= read_time_out {
+                last_received = now;
+
+                // Nobody is listening anymore.
+                if tx.send(Ok(None)).is_err() {
+                    return Ok(());
                 }
             }
         }
-    });
 
-    Ok((
-        move || {
-            t_sender
-                .send(())
-                .expect("An error occurred while terminating child thread");
-        },
-        rx,
-    ))
+        // Wake up in time for the next ping and the termination check.
+        socket.set_read_timeout(Some(
+            next_ping
+                .saturating_duration_since(now)
+                .max(Duration::from_millis(1)),
+        ))?;
+
+        let (len, src_addr) = match socket.recv_from(&mut bufs) {
+            Ok(received) => received,
+            Err(err) => match err.kind() {
+                // Read timeout is reported as `TimedOut` on Windows.
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => continue,
+                _ => return send_err(err.into()),
+            },
+        };
+
+        // Our own broadcast ping is received as well.
+        if bufs[..len].first() != Some(&UNCONNECTED_PONG_ID) {
+            continue;
+        }
+
+        last_received = Instant::now();
+
+        let result = process_unconnected_pong(bufs[..len].to_vec(), src_addr).map(|server| {
+            Some(BedrockLanServer {
+                addr: src_addr,
+                server,
+            })
+        });
+
+        // A malformed pong only concerns its sender, keep listening.
+        if tx.send(result).is_err() {
+            return Ok(());
+        }
+    }
 }
 
+/// Extract the MOTD and port from a `[MOTD]...[/MOTD][AD]...[/AD]` broadcast message.
 fn abstract_broadcast_message(message: &str) -> Result<(&str, u16), MspErr> {
-    let motd_start = message.find(BROADCAST_MUST_CONTAIN[0]).unwrap();
-    let motd_end = message.find(BROADCAST_MUST_CONTAIN[1]).unwrap();
-    let port_start = message.find(BROADCAST_MUST_CONTAIN[2]).unwrap();
-    let port_end = message.find(BROADCAST_MUST_CONTAIN[3]).unwrap();
-
-    match (
-        &message[motd_start + BROADCAST_MUST_CONTAIN[0].len()..motd_end],
-        &message[port_start + BROADCAST_MUST_CONTAIN[2].len()..port_end],
-    ) {
-        (motd, port) => match port.parse::<u16>() {
-            Ok(p) => Ok((motd, p)),
-            Err(_) => Err(MspErr::DataErr(format!(
-                "Can not parse {} into port number",
-                port
-            ))),
-        },
+    let motd = tag_content(
+        message,
+        BROADCAST_MUST_CONTAIN[0],
+        BROADCAST_MUST_CONTAIN[1],
+    )?;
+    let port = tag_content(
+        message,
+        BROADCAST_MUST_CONTAIN[2],
+        BROADCAST_MUST_CONTAIN[3],
+    )?;
+
+    match port.parse::<u16>() {
+        Ok(p) => Ok((motd, p)),
+        Err(_) => Err(MspErr::DataErr(format!(
+            "Can not parse {} into port number",
+            port
+        ))),
+    }
+}
+
+/// Text between the first `start_tag` and the `end_tag` following it.
+fn tag_content<'a>(message: &'a str, start_tag: &str, end_tag: &str) -> Result<&'a str, MspErr> {
+    let start = message
+        .find(start_tag)
+        .ok_or_else(|| MspErr::DataErr(format!("Missing {} in broadcast message", start_tag)))?
+        + start_tag.len();
+    let len = message[start..].find(end_tag).ok_or_else(|| {
+        MspErr::DataErr(format!(
+            "Missing {} after {} in broadcast message",
+            end_tag, start_tag
+        ))
+    })?;
+
+    Ok(&message[start..start + len])
+}
+
+#[cfg(test)]
+mod lan_test {
+    use super::*;
+
+    #[test]
+    fn test_lan_servers_skip_none() {
+        let (tx, rx) = mpsc::channel();
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 4445));
+
+        tx.send(Ok(None)).unwrap();
+        tx.send(Ok(Some(LanServer::create(addr, "A Server".into(), 25565))))
+            .unwrap();
+        tx.send(Ok(None)).unwrap();
+        drop(tx);
+
+        let servers = LanServers { rx }.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(
+            servers,
+            vec![LanServer::create(addr, "A Server".into(), 25565)]
+        );
+    }
+
+    #[test]
+    fn test_receive_broadcast_after_malformed() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let socket_conf = SocketConf {
+            read_time_out: Some(Duration::from_millis(50)),
+            lan_heartbeat: false,
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::channel();
+        let (t_sender, t_receiver) = mpsc::channel();
+
+        socket.set_read_timeout(socket_conf.read_time_out).unwrap();
+
+        let thread = std::thread::spawn(move || {
+            // Leaving the group fails as it was never joined, only the sent results matter.
+            let _ = receive_broadcast(
+                socket,
+                IpAddr::V4(MULTICAST_ADDR),
+                &socket_conf,
+                tx,
+                t_receiver,
+            );
+        });
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        for message in [
+            &b"[MOTD]A Server[/MOTD][AD]port[/AD]"[..],
+            b"[MOTD]\xFF[/MOTD][AD]25565[/AD]",
+            b"[MOTD]A Server[/MOTD][AD]25565[/AD]",
+        ] {
+            sender.send_to(message, addr).unwrap();
+        }
+
+        assert!(matches!(rx.recv().unwrap(), Err(MspErr::DataErr(_))));
+        assert!(matches!(rx.recv().unwrap(), Err(MspErr::DataErr(_))));
+        assert_eq!(
+            rx.recv().unwrap().unwrap(),
+            Some(LanServer::create(
+                sender.local_addr().unwrap(),
+                "A Server".into(),
+                25565
+            ))
+        );
+
+        t_sender.send(()).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_abstract_broadcast_message() {
+        assert_eq!(
+            abstract_broadcast_message("[MOTD]A Server[/MOTD][AD]25565[/AD]\0\0").unwrap(),
+            ("A Server", 25565)
+        );
+
+        for message in [
+            "[/MOTD][MOTD]A Server[AD]25565[/AD]",
+            "[MOTD]A Server[/MOTD][/AD]25565[AD]",
+            "[MOTD]A Server[/MOTD][AD]255",
+            "[MOTD]A Server[/MOTD][AD]port[/AD]",
+        ] {
+            assert!(matches!(
+                abstract_broadcast_message(message),
+                Err(MspErr::DataErr(_))
+            ));
+        }
     }
 }
@@ -84,20 +84,14 @@ use std::time::Duration;
 const SERVER_OFFLINE_TIMEOUT: u64 = 2000;
 
 fn main() -> Result<(), MspErr> {
-    let (_ter, receiver) = get_lan_server_status(&SocketConf {
+    let (_discovery, servers) = get_lan_server_status(&SocketConf {
         read_time_out: Some(Duration::from_millis(SERVER_OFFLINE_TIMEOUT)),
         ..Default::default()
     })?;
 
-    loop {
-        match receiver.recv() {
-            Ok(result) => {
-                if let Ok(Some(server)) = result {
-                    // ...
-                }
-            }
-            Err(_) => {}
-        }
+    for server in servers {
+        let server = server?;
+        // ...
     }
 
     Ok(())
@@ -129,6 +123,16 @@ query.port=25565 # Configure the port according to your specific situation
 
 Make sure to save the changes and restart the server for the configuration to take effect.
 
+### Async support
+
+Enable the `tokio` feature to get an async counterpart of every status method on [Conf],
+such as `Conf::get_server_status_async`. The return types are the same as the blocking versions.
+
+```toml
+[dependencies]
+msp = { version = "0.1.2", features = ["tokio"] }
+```
+
 ### License
 
 MIT.
@@ -136,16 +140,35 @@ MIT.
 
 #![warn(missing_docs)]
 
+mod batch;
 mod conf;
+mod dns;
 mod error;
 mod lan;
+mod motd;
+mod protocol;
 mod query;
 mod server;
 mod share;
+mod socks;
 mod varint;
 
-pub use conf::{Conf, SocketConf};
+pub use batch::get_server_status_batch;
+#[cfg(feature = "tokio")]
+pub use batch::get_server_status_batch_async;
+pub use conf::{
+    Conf, ConfBuilder, IpPreference, ProxyConf, Resolver, ServerSnapshot, SocketConf, StatusResult,
+    BEDROCK_DEFAULT_PORT, JAVA_DEFAULT_PORT,
+};
 pub use error::MspErr;
-pub use lan::{get_lan_server_status, LanServer};
-pub use query::{QueryBasic, QueryFull};
-pub use server::{BedrockServer, LegacyBetaServer, LegacyServer, NettyServer, Server};
+pub use lan::{
+    get_bedrock_lan_server_status, get_lan_server_status, get_lan_server_status_for,
+    BedrockLanServer, LanDiscovery, LanServer, LanServers,
+};
+pub use protocol::protocol_to_version_names;
+pub use query::{QueryBasic, QueryFull, QueryToken};
+pub use server::{
+    get_server_status_on, BedrockServer, Description, DescriptionExtra, GameMode, LegacyBetaServer,
+    LegacyServer, NettyServer, Player, ProtocolSupport, Server, ServerKind, ServerStatus,
+};
+pub use share::{PacketStream, ToJson};
@@ -0,0 +1,312 @@
+use crate::server::{Description, DescriptionExtra};
+
+/// Prefix of the Minecraft [formatting codes](https://wiki.vg/Chat#Colors).
+const FORMATTING_CODE_PREFIX: char = '§';
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Color codes, their chat component names, the corresponding ANSI color and the RGB
+/// color of the vanilla client.
+const COLORS: [(char, &str, u8, u32); 16] = [
+    ('0', "black", 30, 0x000000),
+    ('1', "dark_blue", 34, 0x0000AA),
+    ('2', "dark_green", 32, 0x00AA00),
+    ('3', "dark_aqua", 36, 0x00AAAA),
+    ('4', "dark_red", 31, 0xAA0000),
+    ('5', "dark_purple", 35, 0xAA00AA),
+    ('6', "gold", 33, 0xFFAA00),
+    ('7', "gray", 37, 0xAAAAAA),
+    ('8', "dark_gray", 90, 0x555555),
+    ('9', "blue", 94, 0x5555FF),
+    ('a', "green", 92, 0x55FF55),
+    ('b', "aqua", 96, 0x55FFFF),
+    ('c', "red", 91, 0xFF5555),
+    ('d', "light_purple", 95, 0xFF55FF),
+    ('e', "yellow", 93, 0xFFFF55),
+    ('f', "white", 97, 0xFFFFFF),
+];
+
+/// Text style shared by chat components and formatting codes.
+#[derive(Clone, Default)]
+struct Style {
+    color: String,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Style {
+    /// Style of the component, fields not set are inherited from parent.
+    fn inherit(parent: &Style, extra: &DescriptionExtra) -> Self {
+        Self {
+            color: match extra.color.is_empty() {
+                true => parent.color.clone(),
+                false => extra.color.clone(),
+            },
+            bold: parent.bold || extra.bold,
+            italic: parent.italic || extra.italic,
+            underlined: parent.underlined || extra.underlined,
+            strikethrough: parent.strikethrough || extra.strikethrough,
+            obfuscated: parent.obfuscated || extra.obfuscated,
+        }
+    }
+
+    /// Style of the root component of the description.
+    fn root(description: &Description) -> Self {
+        Self {
+            color: description.color.clone(),
+            bold: description.bold,
+            italic: description.italic,
+            underlined: description.underlined,
+            strikethrough: description.strikethrough,
+            obfuscated: description.obfuscated,
+        }
+    }
+
+    /// Apply a formatting code, return false if the code is unknown.
+    ///
+    /// As in the vanilla client, a color code also resets the formatting,
+    /// and `§r` resets to the base style.
+    fn apply_code(&mut self, code: char, base: &Style) -> bool {
+        let code = code.to_ascii_lowercase();
+
+        if let Some((_, name, _, _)) = COLORS.iter().find(|(c, _, _, _)| *c == code) {
+            *self = Style {
+                color: name.to_string(),
+                ..Default::default()
+            };
+
+            return true;
+        }
+
+        match code {
+            'k' => self.obfuscated = true,
+            'l' => self.bold = true,
+            'm' => self.strikethrough = true,
+            'n' => self.underlined = true,
+            'o' => self.italic = true,
+            'r' => *self = base.clone(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn to_extra(&self, text: String) -> DescriptionExtra {
+        DescriptionExtra {
+            color: self.color.clone(),
+            bold: self.bold,
+            italic: self.italic,
+            underlined: self.underlined,
+            strikethrough: self.strikethrough,
+            obfuscated: self.obfuscated,
+            text,
+            ..Default::default()
+        }
+    }
+
+    fn to_ansi(&self) -> String {
+        let mut codes = Vec::<String>::new();
+
+        if let Some((_, _, ansi, _)) = COLORS.iter().find(|(_, name, _, _)| *name == self.color) {
+            codes.push(ansi.to_string());
+        } else if let Some((r, g, b)) = parse_hex_color(&self.color) {
+            codes.push(format!("38;2;{};{};{}", r, g, b));
+        }
+
+        for (enabled, code) in [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underlined, "4"),
+            (self.obfuscated, "5"),
+            (self.strikethrough, "9"),
+        ] {
+            if enabled {
+                codes.push(code.into());
+            }
+        }
+
+        match codes.is_empty() {
+            true => "".into(),
+            false => format!("\x1b[{}m", codes.join(";")),
+        }
+    }
+}
+
+/// Parse `#rrggbb` color used since 1.16.
+fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+
+    Some(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+/// RGB of a named or `#rrggbb` color, `None` if empty or unknown.
+pub fn color_to_rgb(color: &str) -> Option<(u8, u8, u8)> {
+    match COLORS.iter().find(|(_, name, _, _)| *name == color) {
+        Some((_, _, _, rgb)) => Some(((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8)),
+        None => parse_hex_color(color),
+    }
+}
+
+/// Split `§`-coded text into styled segments, starting from the `base` style.
+fn parse_with_base(text: &str, base: &Style) -> Vec<(Style, String)> {
+    let mut result = Vec::new();
+    let mut style = base.clone();
+    let mut segment = String::new();
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != FORMATTING_CODE_PREFIX {
+            segment.push(ch);
+            continue;
+        }
+
+        // Unknown codes and a dangling prefix are dropped as well.
+        if let Some(code) = chars.next() {
+            let mut next_style = style.clone();
+
+            if next_style.apply_code(code, base) {
+                if !segment.is_empty() {
+                    result.push((style, std::mem::take(&mut segment)));
+                }
+
+                style = next_style;
+            }
+        }
+    }
+
+    if !segment.is_empty() {
+        result.push((style, segment));
+    }
+
+    result
+}
+
+/// Flatten the chat component tree into styled segments.
+fn flatten(description: &Description) -> Vec<(Style, String)> {
+    fn flatten_extra(extra: &DescriptionExtra, parent: &Style, result: &mut Vec<(Style, String)>) {
+        let style = Style::inherit(parent, extra);
+
+        result.append(&mut parse_with_base(&extra.text, &style));
+
+        for child in &extra.extra {
+            flatten_extra(child, &style, result);
+        }
+    }
+
+    let base = Style::root(description);
+    let mut result = parse_with_base(&description.text, &base);
+
+    for extra in &description.extra {
+        flatten_extra(extra, &base, &mut result);
+    }
+
+    result
+}
+
+/// Parse a `§`-coded text into chat components, one per styled segment.
+pub fn parse_formatting_codes(text: &str) -> Vec<DescriptionExtra> {
+    parse_with_base(text, &Style::default())
+        .into_iter()
+        .map(|(style, text)| style.to_extra(text))
+        .collect()
+}
+
+/// Readable text of the description, without any formatting.
+pub fn description_to_plain(description: &Description) -> String {
+    flatten(description)
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect()
+}
+
+/// Text of the description with formatting converted to ANSI escape sequences.
+pub fn description_to_ansi(description: &Description) -> String {
+    let mut result = String::new();
+
+    for (style, text) in flatten(description) {
+        result.push_str(ANSI_RESET);
+        result.push_str(&style.to_ansi());
+        result.push_str(&text);
+    }
+
+    if !result.is_empty() {
+        result.push_str(ANSI_RESET);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod motd_test {
+    use super::*;
+
+    fn strip(text: &str) -> String {
+        description_to_plain(&Description::from_legacy(text))
+    }
+
+    #[test]
+    fn test_strip_formatting_codes() {
+        assert_eq!(strip("§cHello §l§nWorld§r!"), "Hello World!");
+        assert_eq!(strip("No codes"), "No codes");
+        assert_eq!(strip("Dangling§"), "Dangling");
+    }
+
+    #[test]
+    fn test_parse_formatting_codes() {
+        let extra = parse_formatting_codes("§cRed §lBold§aGreen");
+
+        assert_eq!(extra.len(), 3);
+        assert_eq!(
+            (extra[0].color.as_str(), extra[0].text.as_str()),
+            ("red", "Red ")
+        );
+        assert!(!extra[0].bold);
+        assert_eq!(
+            (extra[1].color.as_str(), extra[1].text.as_str()),
+            ("red", "Bold")
+        );
+        assert!(extra[1].bold);
+        // Color codes reset formatting.
+        assert_eq!(
+            (extra[2].color.as_str(), extra[2].text.as_str()),
+            ("green", "Green")
+        );
+        assert!(!extra[2].bold);
+    }
+
+    #[test]
+    fn test_description_to_plain_and_ansi() {
+        let description = Description {
+            text: "§eA ".into(),
+            extra: vec![DescriptionExtra {
+                color: "#FF0000".into(),
+                bold: true,
+                text: "Minecraft".into(),
+                extra: vec![DescriptionExtra {
+                    text: " Server".into(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(description_to_plain(&description), "A Minecraft Server");
+        assert_eq!(description.extra[0].color_rgb(), Some((255, 0, 0)));
+        assert_eq!(color_to_rgb("gold"), Some((255, 170, 0)));
+        assert_eq!(color_to_rgb(""), None);
+        assert_eq!(color_to_rgb("#FFF"), None);
+        assert_eq!(
+            description_to_ansi(&description),
+            "\x1b[0m\x1b[93mA \x1b[0m\x1b[38;2;255;0;0;1mMinecraft\x1b[0m\x1b[38;2;255;0;0;1m Server\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+/// Release [protocol version numbers](https://wiki.vg/Protocol_version_numbers) since the
+/// Netty rewrite (1.7), and the versions using them.
+const PROTOCOL_VERSIONS: [(i32, &[&str]); 48] = [
+    (4, &["1.7.2", "1.7.3", "1.7.4", "1.7.5"]),
+    (5, &["1.7.6", "1.7.7", "1.7.8", "1.7.9", "1.7.10"]),
+    (
+        47,
+        &[
+            "1.8", "1.8.1", "1.8.2", "1.8.3", "1.8.4", "1.8.5", "1.8.6", "1.8.7", "1.8.8", "1.8.9",
+        ],
+    ),
+    (107, &["1.9"]),
+    (108, &["1.9.1"]),
+    (109, &["1.9.2"]),
+    (110, &["1.9.3", "1.9.4"]),
+    (210, &["1.10", "1.10.1", "1.10.2"]),
+    (315, &["1.11"]),
+    (316, &["1.11.1", "1.11.2"]),
+    (335, &["1.12"]),
+    (338, &["1.12.1"]),
+    (340, &["1.12.2"]),
+    (393, &["1.13"]),
+    (401, &["1.13.1"]),
+    (404, &["1.13.2"]),
+    (477, &["1.14"]),
+    (480, &["1.14.1"]),
+    (485, &["1.14.2"]),
+    (490, &["1.14.3"]),
+    (498, &["1.14.4"]),
+    (573, &["1.15"]),
+    (575, &["1.15.1"]),
+    (578, &["1.15.2"]),
+    (735, &["1.16"]),
+    (736, &["1.16.1"]),
+    (751, &["1.16.2"]),
+    (753, &["1.16.3"]),
+    (754, &["1.16.4", "1.16.5"]),
+    (755, &["1.17"]),
+    (756, &["1.17.1"]),
+    (757, &["1.18", "1.18.1"]),
+    (758, &["1.18.2"]),
+    (759, &["1.19"]),
+    (760, &["1.19.1", "1.19.2"]),
+    (761, &["1.19.3"]),
+    (762, &["1.19.4"]),
+    (763, &["1.20", "1.20.1"]),
+    (764, &["1.20.2"]),
+    (765, &["1.20.3", "1.20.4"]),
+    (766, &["1.20.5", "1.20.6"]),
+    (767, &["1.21", "1.21.1"]),
+    (768, &["1.21.2", "1.21.3"]),
+    (769, &["1.21.4"]),
+    (770, &["1.21.5"]),
+    (771, &["1.21.6"]),
+    (772, &["1.21.7", "1.21.8"]),
+    (773, &["1.21.9", "1.21.10"]),
+];
+
+/// Release protocol version numbers before the Netty rewrite, for the versions
+/// answering the legacy server list ping (1.4 to 1.6).
+const LEGACY_PROTOCOL_VERSIONS: [(i32, &[&str]); 9] = [
+    (47, &["1.4.2"]),
+    (49, &["1.4.4", "1.4.5"]),
+    (51, &["1.4.6", "1.4.7"]),
+    (60, &["1.5", "1.5.1"]),
+    (61, &["1.5.2"]),
+    (73, &["1.6.1"]),
+    (74, &["1.6.2"]),
+    (77, &["1.6.3"]),
+    (78, &["1.6.4"]),
+];
+
+fn lookup(table: &[(i32, &[&'static str])], protocol: i32) -> Vec<&'static str> {
+    table
+        .iter()
+        .find(|(num, _)| *num == protocol)
+        .map_or(vec![], |(_, names)| names.to_vec())
+}
+
+/// Minecraft Java Edition release versions using the given protocol version number,
+/// as reported by modern servers in [Server::version](crate::Server::version).
+///
+/// Returns an empty list for snapshots, pre-releases and unknown numbers.
+/// Only the protocol numbers since 1.7 are covered, see [LegacyServer::minecraft_versions](crate::LegacyServer::minecraft_versions)
+/// for the older ones.
+///
+/// # Example
+///
+/// ```
+/// use msp::protocol_to_version_names;
+///
+/// assert_eq!(protocol_to_version_names(763), vec!["1.20", "1.20.1"]);
+/// assert!(protocol_to_version_names(-1).is_empty());
+/// ```
+pub fn protocol_to_version_names(protocol: i32) -> Vec<&'static str> {
+    lookup(&PROTOCOL_VERSIONS, protocol)
+}
+
+/// Same as [protocol_to_version_names], for the protocol numbers before 1.7.
+pub fn legacy_protocol_to_version_names(protocol: i32) -> Vec<&'static str> {
+    lookup(&LEGACY_PROTOCOL_VERSIONS, protocol)
+}
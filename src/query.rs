@@ -1,15 +1,77 @@
 use crate::{
     conf::Conf,
-    share::{create_udp_socket, UdpReader},
-    MspErr,
+    share::{
+        check_udp_proxy, create_udp_socket_for, display_json, resolve_conf_addrs, send_datagram,
+        trim_trailing_nul, ToJson, UdpPeek, UdpReader, UDP_MAX_DATAGRAM_SIZE,
+    },
+    Description, IpPreference, MspErr, SocketConf,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::Instant,
 };
-use serde::Serialize;
 
-const TOKEN_MASK: i32 = 0x0F0F0F0F;
+/// Session ID sent by the client, see [build_handshake_packet].
+const SESSION_ID: i32 = 1;
+/// Servers only keep the lower 4 bits of each byte of the session ID.
+const SESSION_ID_MASK: i32 = 0x0F0F0F0F;
+/// Type of the handshake request and response.
+const HANDSHAKE_TYPE: u8 = 0x09;
+/// Type of the stat request and response.
+const STAT_TYPE: u8 = 0x00;
 const PENDDING_BUFS: [u8; 4] = [0x00, 0x00, 0x00, 0x00];
+/// Padding before the fragment index of full stat response.
+const SPLITNUM: &[u8] = b"splitnum\0";
+/// Name of the player section of the full stat response, after the `\x01` padding.
+const PLAYER_SECTION_NAME: &str = "player_";
+/// Type, Session ID, `splitnum\0`, fragment index and a padding byte.
+const FULL_STAT_HEADER_LEN: usize = 16;
+
+/// Challenge token of a [Query](https://wiki.vg/Query) handshake, see [Conf::query_handshake].
+///
+/// The server only accepts the token from the local address the handshake was sent from,
+/// which is reused for the stat requests. Vanilla servers expire tokens after 30 seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryToken {
+    /// Challenge token sent back in stat requests.
+    pub token: i32,
+    /// Server address the handshake was sent to.
+    pub server_addr: SocketAddr,
+    /// Local address the handshake was sent from.
+    pub local_addr: SocketAddr,
+    /// When the handshake response was received.
+    pub received_at: Instant,
+}
+
+impl QueryToken {
+    fn create(token: i32, server_addr: SocketAddr, local_addr: SocketAddr) -> Self {
+        Self {
+            token,
+            server_addr,
+            local_addr,
+            received_at: Instant::now(),
+        }
+    }
+
+    /// Socket configuration binding to [QueryToken::local_addr].
+    fn socket_conf(&self, socket_conf: &SocketConf) -> SocketConf {
+        let mut socket_conf = SocketConf {
+            rep_udp_port: self.local_addr.port(),
+            ..socket_conf.clone()
+        };
+
+        match self.local_addr.ip() {
+            IpAddr::V4(ip) => socket_conf.rep_udp_ipv4 = ip,
+            IpAddr::V6(ip) => socket_conf.rep_udp_ipv6 = ip,
+        }
+
+        socket_conf
+    }
+}
 
 /// [Basic stat](https://wiki.vg/Query#Basic_stat) in [Query](https://wiki.vg/Query) protocol.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct QueryBasic {
     /// MOTD of the target server.
     pub motd: String,
@@ -30,16 +92,31 @@ pub struct QueryBasic {
 
 impl std::fmt::Display for QueryBasic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
+    }
+}
+
+impl ToJson for QueryBasic {}
+
+impl QueryBasic {
+    /// MOTD parsed into a structured [Description], see [Description::from_legacy].
+    pub fn description(&self) -> Description {
+        Description::from_legacy(&self.motd)
+    }
+
+    /// [QueryBasic::online_players] parsed into number.
+    pub fn online_players(&self) -> Result<u32, MspErr> {
+        parse_player_count("numplayers", &self.online_players)
+    }
+
+    /// [QueryBasic::maxplayers] parsed into number.
+    pub fn max_players(&self) -> Result<u32, MspErr> {
+        parse_player_count("maxplayers", &self.maxplayers)
     }
 }
 
 /// [Full stat](https://wiki.vg/Query#Full_stat) in [Query](https://wiki.vg/Query) protocol.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct QueryFull {
     /// Server host.
     pub hostname: String,
@@ -67,15 +144,25 @@ pub struct QueryFull {
 
 impl std::fmt::Display for QueryFull {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
     }
 }
 
-#[derive(Serialize, Debug)]
+impl ToJson for QueryFull {}
+
+impl QueryFull {
+    /// [QueryFull::online_players] parsed into number.
+    pub fn online_players(&self) -> Result<u32, MspErr> {
+        parse_player_count("numplayers", &self.online_players)
+    }
+
+    /// [QueryFull::maxplayers] parsed into number.
+    pub fn max_players(&self) -> Result<u32, MspErr> {
+        parse_player_count("maxplayers", &self.maxplayers)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ModPlugin {
     mod_name: String,
     plugins: Vec<String>,
@@ -83,122 +170,210 @@ pub struct ModPlugin {
 
 impl std::fmt::Display for ModPlugin {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
     }
 }
 
-fn send_query_request(conf: &Conf, full_query: bool) -> Result<UdpReader, MspErr> {
-    let socket = create_udp_socket(&conf.socket_conf)?;
+impl ToJson for ModPlugin {}
+
+/// Player counts are sent as strings in the Query protocol.
+fn parse_player_count(field: &str, value: &str) -> Result<u32, MspErr> {
+    value
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| MspErr::DataErr(format!("Can not parse {} into {}", value, field)))
+}
+
+/// Send handshake, return the socket and challenge token.
+fn send_handshake(conf: &Conf) -> Result<(UdpSocket, i32), MspErr> {
+    check_udp_proxy(conf)?;
+
+    // IPv4 is preferred, see [SocketConf::rep_udp_ipv6](crate::SocketConf::rep_udp_ipv6).
+    let addr = resolve_conf_addrs(conf, IpPreference::Ipv4)?[0];
+    let socket = create_udp_socket_for(&conf.socket_conf, &addr)?;
     let mut bufs = [0u8; 17];
-    // Construct init packet
-    //
-    // Magic num: 0xFE, 0xFD
-    // Type: 0x09 - for handshake, 0x00 - for status request
-    // Session ID: for convenience, set the session_id to 1([0x00, 0x00, 0x00, 0x01])
-    let init_packet: &mut [u8] = &mut [0xFE, 0xFD, 0x09, 0x00, 0x00, 0x00, 0x01];
 
-    socket.connect(conf)?;
-    socket.send(init_packet)?;
+    socket.connect(addr)?;
+    send_datagram(&socket, &build_handshake_packet())?;
     socket.recv(&mut bufs)?;
 
-    let (session_id, token) = get_challenge_token(&mut bufs)?;
+    let token = check_handshake_response(&bufs)?;
 
-    if session_id != 1 {
-        return Err(MspErr::DataErr(format!(
-            "Response session_id({}) is inconsistent with the client(1).",
-            session_id
-        )));
+    Ok((socket, token))
+}
+
+/// Async version of [send_handshake].
+#[cfg(feature = "tokio")]
+async fn send_handshake_async(conf: &Conf) -> Result<(tokio::net::UdpSocket, i32), MspErr> {
+    use crate::share::{
+        create_udp_socket_for_async, resolve_conf_addrs_async, send_datagram_async, timeout_async,
+    };
+
+    check_udp_proxy(conf)?;
+
+    let addr = resolve_conf_addrs_async(conf, IpPreference::Ipv4).await?[0];
+    let socket = create_udp_socket_for_async(&conf.socket_conf, &addr).await?;
+    let mut bufs = [0u8; 17];
+
+    socket.connect(addr).await?;
+    send_datagram_async(
+        &socket,
+        &build_handshake_packet(),
+        conf.socket_conf.write_timeout,
+    )
+    .await?;
+    timeout_async(conf.socket_conf.read_time_out, socket.recv(&mut bufs)).await?;
+
+    let token = check_handshake_response(&bufs)?;
+
+    Ok((socket, token))
+}
+
+/// Full stat response split into multiple datagrams.
+///
+/// Every datagram repeats Type and Session ID, followed by `splitnum\0`, the fragment index
+/// (with 0x80 set on the last one) and a padding byte. The payloads are joined in index order.
+#[derive(Default)]
+struct FullStatFragments {
+    fragments: Vec<Option<Vec<u8>>>,
+    total: Option<usize>,
+}
+
+impl FullStatFragments {
+    /// Collect a datagram, return the reassembled response once all fragments are received.
+    fn push(&mut self, bufs: &[u8]) -> Result<Option<Vec<u8>>, MspErr> {
+        check_stat_response(bufs)?;
+
+        let (idx, is_last) = match (bufs.get(5..14), bufs.get(14)) {
+            (Some(SPLITNUM), Some(&num)) => ((num & 0x7F) as usize, num & 0x80 != 0),
+            // Not in split format, take it as a whole.
+            _ => return Ok(Some(bufs.to_vec())),
+        };
+
+        if self.fragments.len() <= idx {
+            self.fragments.resize(idx + 1, None);
+        }
+
+        self.fragments[idx] = Some(bufs.to_vec());
+
+        if is_last {
+            self.total = Some(idx + 1);
+        }
+
+        let total = match self.total {
+            Some(total) if self.fragments.iter().take(total).all(|f| f.is_some()) => total,
+            _ => return Ok(None),
+        };
+        let mut result = Vec::new();
+
+        for (idx, fragment) in self.fragments.drain(..total).flatten().enumerate() {
+            match idx {
+                0 => result.extend_from_slice(&fragment),
+                _ => result
+                    .extend_from_slice(fragment.get(FULL_STAT_HEADER_LEN..).unwrap_or_default()),
+            }
+        }
+
+        Ok(Some(result))
     }
+}
 
-    // Send stat request [packet](https://wiki.vg/Query#Request_2)
+/// Build handshake [packet](https://wiki.vg/Query#Request).
+fn build_handshake_packet() -> Vec<u8> {
+    // Construct init packet
     //
+    // Magic num: 0xFE, 0xFD
+    // Type: 0x09 - for handshake, 0x00 - for status request
+    // Session ID: for convenience, set the session_id to 1([0x00, 0x00, 0x00, 0x01])
+    [
+        [0xFE, 0xFD, HANDSHAKE_TYPE].as_slice(),
+        &SESSION_ID.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// Build stat request [packet](https://wiki.vg/Query#Request_2).
+fn build_stat_request_packet(token: i32, full_query: bool) -> Vec<u8> {
+    let mut packet = build_handshake_packet();
+
     // Should change the type into 0x00
-    init_packet[2] = 0x00;
-    // Reset to 5 bytes
-    let mut bufs = [0u8; 5];
+    packet[2] = STAT_TYPE;
 
     // Full query except the payload must be padded to 8 bytes.
     // Sending [0x00, 0x00, 0x00, 0x00] at the end works.
-    socket.send(
-        &[
-            init_packet,
-            token.to_be_bytes().as_slice(),
-            &mut (match full_query {
-                true => PENDDING_BUFS.as_slice(),
-                false => [].as_slice(),
-            }),
-        ]
-        .concat(),
-    )?;
-    // Use peek instand of recv cause unknown response packet size
-    socket.peek(&mut bufs)?;
+    [
+        packet.as_slice(),
+        token.to_be_bytes().as_slice(),
+        match full_query {
+            true => PENDDING_BUFS.as_slice(),
+            false => [].as_slice(),
+        },
+    ]
+    .concat()
+}
 
-    if bufs.get(0) != Some(&0x00) {
+/// Validate the handshake response [packet](https://wiki.vg/Query#Response) and return the challenge token.
+fn check_handshake_response(bufs: &[u8]) -> Result<i32, MspErr> {
+    // The response is received into a zeroed buffer.
+    let bufs = trim_trailing_nul(bufs);
+
+    if bufs.len() <= 5 || bufs.len() > 17 {
         return Err(MspErr::DataErr(format!(
-            "Response packet invalid, expected start with 0x00, but got: {}",
-            bufs[0]
+            "Query handshake response packet len invalid, current len: {}",
+            bufs.len()
         )));
     }
 
-    match bufs[1..].try_into() {
-        Ok(bs) => {
-            let receive_token = i32::from_be_bytes(bs) & TOKEN_MASK;
-
-            if receive_token != 1 {
-                return Err(MspErr::DataErr(format!(
-                    "Query session ID mismatch, expected: {}, but got: {}",
-                    token, receive_token
-                )));
-            }
-
-            // Set Reader index to 5. We don't need Type and Session ID anymore.
-            Ok(UdpReader::create_with_idx(socket, 5))
-        }
-        Err(err) => Err(MspErr::InternalErr(err.to_string())),
-    }
+    check_response_header(bufs, HANDSHAKE_TYPE)?;
+    parse_challenge_token(&bufs[5..])
 }
 
-/// Process query handshake response [packet](https://wiki.vg/Query#Response), and get challenge token.
-fn get_challenge_token(mut bufs: &mut [u8]) -> Result<(i32, i32), MspErr> {
-    // Remove the 0 element at the end of the array
-    let mut buf_len = bufs.len();
-    while let Some(&0) = bufs.last() {
-        bufs = &mut bufs[..buf_len - 1];
-        buf_len = bufs.len();
-    }
+/// Validate the first 5 bytes (Type and Session ID) of the stat response.
+fn check_stat_response(bufs: &[u8]) -> Result<(), MspErr> {
+    check_response_header(bufs, STAT_TYPE)
+}
 
-    if buf_len <= 5 || buf_len > 17 {
+/// Check the Type and the Session ID the server echoes at the start of every response.
+fn check_response_header(bufs: &[u8], packet_type: u8) -> Result<(), MspErr> {
+    if bufs.first() != Some(&packet_type) {
         return Err(MspErr::DataErr(format!(
-            "Query handshake response packet len invalid, current len: {}",
-            buf_len
+            "Response packet invalid, expected start with 0x{:02X}, but got: {:?}",
+            packet_type,
+            bufs.first()
         )));
     }
 
-    if bufs.get(0) != Some(&0x09) {
-        return Err(MspErr::DataErr(format!(
-            "Query handshake response packet invalid, expected start with 0x90, but got: {}",
-            bufs[0]
-        )));
+    match bufs.get(1..5).unwrap_or_default().try_into() {
+        Ok(bs) => check_session_id(i32::from_be_bytes(bs)),
+        Err(_) => Err(MspErr::DataErr(format!(
+            "Response packet too short for the session ID: {:?}",
+            bufs
+        ))),
     }
+}
 
-    let session_id = i32::from_be_bytes(match bufs[1..5].try_into() {
-        Ok(id) => id,
-        Err(err) => {
-            return Err(MspErr::DataErr(format!(
-                "Can not parse bufs into session_id, bufs: {:?}, reason: {}.",
-                bufs[1..5].to_vec(),
-                err.to_string()
-            )));
-        }
-    }) & TOKEN_MASK;
+/// Compare the session ID echoed by the server with [SESSION_ID], both masked.
+fn check_session_id(session_id: i32) -> Result<(), MspErr> {
+    match session_id & SESSION_ID_MASK == SESSION_ID & SESSION_ID_MASK {
+        true => Ok(()),
+        false => Err(MspErr::DataErr(format!(
+            "Query session ID mismatch, expected: {}, but got: {}",
+            SESSION_ID, session_id
+        ))),
+    }
+}
 
-    match std::str::from_utf8(&bufs[5..]) {
-        Ok(token_str) => match token_str.parse::<i32>() {
-            Ok(token) => Ok((session_id, token)),
+/// Parse the challenge token, sent as a decimal string after the session ID of the handshake response.
+fn parse_challenge_token(bufs: &[u8]) -> Result<i32, MspErr> {
+    // The token is a signed 32-bit integer, some servers print it as unsigned though.
+    // Either way it is sent back as is, as its 4 big-endian bytes.
+    match std::str::from_utf8(bufs) {
+        Ok(token_str) => match token_str.parse::<i64>() {
+            Ok(token) if (i32::MIN as i64..=u32::MAX as i64).contains(&token) => Ok(token as i32),
+            Ok(token) => Err(MspErr::DataErr(format!(
+                "Challenge token out of 32-bit range: {}",
+                token
+            ))),
             Err(err) => Err(MspErr::InternalErr(err.to_string())),
         },
         Err(err) => Err(MspErr::InternalErr(err.to_string())),
@@ -207,8 +382,289 @@ fn get_challenge_token(mut bufs: &mut [u8]) -> Result<(i32, i32), MspErr> {
 
 /// Get basic [status](https://wiki.vg/Query#Basic_stat)
 pub fn query_basic_status(conf: &Conf) -> Result<QueryBasic, MspErr> {
-    let mut udp_reader = send_query_request(conf, false)?;
+    let (socket, token) = send_handshake(conf)?;
+
+    request_basic_stat(&socket, token)
+}
+
+/// Async version of [query_basic_status].
+#[cfg(feature = "tokio")]
+pub async fn query_basic_status_async(conf: &Conf) -> Result<QueryBasic, MspErr> {
+    let (socket, token) = send_handshake_async(conf).await?;
+
+    request_basic_stat_async(&socket, token, conf).await
+}
+
+/// Get full [status](https://wiki.vg/Query#Full_stat)
+///
+/// Responses split into multiple datagrams (e.g. long player lists) are reassembled.
+pub fn query_full_status(conf: &Conf) -> Result<QueryFull, MspErr> {
+    let (socket, token) = send_handshake(conf)?;
+
+    request_full_stat(&socket, token)
+}
+
+/// Async version of [query_full_status].
+#[cfg(feature = "tokio")]
+pub async fn query_full_status_async(conf: &Conf) -> Result<QueryFull, MspErr> {
+    let (socket, token) = send_handshake_async(conf).await?;
+
+    request_full_stat_async(&socket, token, conf).await
+}
+
+/// Get only the player names of the full status.
+pub fn query_players(conf: &Conf) -> Result<Vec<String>, MspErr> {
+    let (socket, token) = send_handshake(conf)?;
+
+    process_query_players_reader(UdpReader::create_with_idx(
+        request_full_stat_bufs(&socket, token)?,
+        5,
+    ))
+}
+
+/// Async version of [query_players].
+#[cfg(feature = "tokio")]
+pub async fn query_players_async(conf: &Conf) -> Result<Vec<String>, MspErr> {
+    let (socket, token) = send_handshake_async(conf).await?;
+    let response = request_full_stat_bufs_async(&socket, token, conf).await?;
+
+    process_query_players_reader(UdpReader::create_with_idx(response, 5))
+}
+
+/// Get both basic and full status, with the challenge token of a single handshake.
+pub fn query_all_status(conf: &Conf) -> Result<(QueryBasic, QueryFull), MspErr> {
+    let (socket, token) = send_handshake(conf)?;
+
+    Ok((
+        request_basic_stat(&socket, token)?,
+        request_full_stat(&socket, token)?,
+    ))
+}
+
+/// Async version of [query_all_status].
+#[cfg(feature = "tokio")]
+pub async fn query_all_status_async(conf: &Conf) -> Result<(QueryBasic, QueryFull), MspErr> {
+    let (socket, token) = send_handshake_async(conf).await?;
+
+    Ok((
+        request_basic_stat_async(&socket, token, conf).await?,
+        request_full_stat_async(&socket, token, conf).await?,
+    ))
+}
+
+/// Send the handshake only, keeping the challenge token for later stat requests.
+pub fn query_handshake(conf: &Conf) -> Result<QueryToken, MspErr> {
+    let (socket, token) = send_handshake(conf)?;
+
+    Ok(QueryToken::create(
+        token,
+        socket.peer_addr()?,
+        socket.local_addr()?,
+    ))
+}
+
+/// Async version of [query_handshake].
+#[cfg(feature = "tokio")]
+pub async fn query_handshake_async(conf: &Conf) -> Result<QueryToken, MspErr> {
+    let (socket, token) = send_handshake_async(conf).await?;
+
+    Ok(QueryToken::create(
+        token,
+        socket.peer_addr()?,
+        socket.local_addr()?,
+    ))
+}
+
+/// Get basic status with the challenge token of a previous [query_handshake].
+pub fn query_basic_status_with_token(
+    conf: &Conf,
+    token: &QueryToken,
+) -> Result<QueryBasic, MspErr> {
+    request_basic_stat(&connect_with_token(conf, token)?, token.token)
+}
+
+/// Async version of [query_basic_status_with_token].
+#[cfg(feature = "tokio")]
+pub async fn query_basic_status_with_token_async(
+    conf: &Conf,
+    token: &QueryToken,
+) -> Result<QueryBasic, MspErr> {
+    let socket = connect_with_token_async(conf, token).await?;
+
+    request_basic_stat_async(&socket, token.token, conf).await
+}
+
+/// Get full status with the challenge token of a previous [query_handshake].
+pub fn query_full_status_with_token(conf: &Conf, token: &QueryToken) -> Result<QueryFull, MspErr> {
+    request_full_stat(&connect_with_token(conf, token)?, token.token)
+}
+
+/// Async version of [query_full_status_with_token].
+#[cfg(feature = "tokio")]
+pub async fn query_full_status_with_token_async(
+    conf: &Conf,
+    token: &QueryToken,
+) -> Result<QueryFull, MspErr> {
+    let socket = connect_with_token_async(conf, token).await?;
+
+    request_full_stat_async(&socket, token.token, conf).await
+}
 
+/// Create the socket the token was obtained from, see [QueryToken].
+fn connect_with_token(conf: &Conf, token: &QueryToken) -> Result<UdpSocket, MspErr> {
+    check_udp_proxy(conf)?;
+
+    let socket = create_udp_socket_for(&token.socket_conf(&conf.socket_conf), &token.server_addr)?;
+
+    socket.connect(token.server_addr)?;
+
+    Ok(socket)
+}
+
+/// Async version of [connect_with_token].
+#[cfg(feature = "tokio")]
+async fn connect_with_token_async(
+    conf: &Conf,
+    token: &QueryToken,
+) -> Result<tokio::net::UdpSocket, MspErr> {
+    use crate::share::create_udp_socket_for_async;
+
+    check_udp_proxy(conf)?;
+
+    let socket =
+        create_udp_socket_for_async(&token.socket_conf(&conf.socket_conf), &token.server_addr)
+            .await?;
+
+    socket.connect(token.server_addr).await?;
+
+    Ok(socket)
+}
+
+/// Send basic stat request on the connected socket and parse the response.
+fn request_basic_stat(socket: &UdpSocket, token: i32) -> Result<QueryBasic, MspErr> {
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    send_datagram(socket, &build_stat_request_packet(token, false))?;
+
+    let len = socket.recv(&mut bufs)?;
+
+    bufs.truncate(len);
+    check_stat_response(&bufs)?;
+
+    // Set Reader index to 5. We don't need Type and Session ID anymore.
+    process_query_basic_reader(UdpReader::create_with_idx(bufs, 5))
+}
+
+/// Async version of [request_basic_stat].
+#[cfg(feature = "tokio")]
+async fn request_basic_stat_async(
+    socket: &tokio::net::UdpSocket,
+    token: i32,
+    conf: &Conf,
+) -> Result<QueryBasic, MspErr> {
+    use crate::share::{send_datagram_async, timeout_async};
+
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    send_datagram_async(
+        socket,
+        &build_stat_request_packet(token, false),
+        conf.socket_conf.write_timeout,
+    )
+    .await?;
+
+    let len = timeout_async(conf.socket_conf.read_time_out, socket.recv(&mut bufs)).await?;
+
+    bufs.truncate(len);
+    check_stat_response(&bufs)?;
+
+    process_query_basic_reader(UdpReader::create_with_idx(bufs, 5))
+}
+
+/// Send full stat request on the connected socket and parse the response.
+fn request_full_stat(socket: &UdpSocket, token: i32) -> Result<QueryFull, MspErr> {
+    process_query_full_reader(UdpReader::create_with_idx(
+        request_full_stat_bufs(socket, token)?,
+        5,
+    ))
+}
+
+/// Async version of [request_full_stat].
+#[cfg(feature = "tokio")]
+async fn request_full_stat_async(
+    socket: &tokio::net::UdpSocket,
+    token: i32,
+    conf: &Conf,
+) -> Result<QueryFull, MspErr> {
+    let response = request_full_stat_bufs_async(socket, token, conf).await?;
+
+    process_query_full_reader(UdpReader::create_with_idx(response, 5))
+}
+
+/// Send the full stat request and receive the reassembled response.
+fn request_full_stat_bufs(socket: &UdpSocket, token: i32) -> Result<Vec<u8>, MspErr> {
+    send_datagram(socket, &build_stat_request_packet(token, true))?;
+
+    recv_full_stat(socket)
+}
+
+/// Async version of [request_full_stat_bufs].
+#[cfg(feature = "tokio")]
+async fn request_full_stat_bufs_async(
+    socket: &tokio::net::UdpSocket,
+    token: i32,
+    conf: &Conf,
+) -> Result<Vec<u8>, MspErr> {
+    use crate::share::send_datagram_async;
+
+    send_datagram_async(
+        socket,
+        &build_stat_request_packet(token, true),
+        conf.socket_conf.write_timeout,
+    )
+    .await?;
+
+    recv_full_stat_async(socket, conf).await
+}
+
+/// Receive the full stat response, see [FullStatFragments].
+fn recv_full_stat(socket: &UdpSocket) -> Result<Vec<u8>, MspErr> {
+    let mut fragments = FullStatFragments::default();
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    loop {
+        let len = socket.recv(&mut bufs)?;
+
+        if let Some(response) = fragments.push(&bufs[..len])? {
+            return Ok(response);
+        }
+    }
+}
+
+/// Async version of [recv_full_stat].
+#[cfg(feature = "tokio")]
+async fn recv_full_stat_async(
+    socket: &tokio::net::UdpSocket,
+    conf: &Conf,
+) -> Result<Vec<u8>, MspErr> {
+    use crate::share::timeout_async;
+
+    let mut fragments = FullStatFragments::default();
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    loop {
+        let len = timeout_async(conf.socket_conf.read_time_out, socket.recv(&mut bufs)).await?;
+
+        if let Some(response) = fragments.push(&bufs[..len])? {
+            return Ok(response);
+        }
+    }
+}
+
+/// Parse basic stat response into [QueryBasic].
+fn process_query_basic_reader<S: UdpPeek>(
+    mut udp_reader: UdpReader<S>,
+) -> Result<QueryBasic, MspErr> {
     Ok(QueryBasic {
         motd: udp_reader.read_nt_str()?,
         game_type: udp_reader.read_nt_str()?,
@@ -220,65 +676,358 @@ pub fn query_basic_status(conf: &Conf) -> Result<QueryBasic, MspErr> {
     })
 }
 
-/// Get full [status](https://wiki.vg/Query#Full_stat)
-pub fn query_full_status(conf: &Conf) -> Result<QueryFull, MspErr> {
-    let mut udp_reader = send_query_request(conf, true)?;
+/// Parse full stat response into [QueryFull].
+fn process_query_full_reader<S: UdpPeek>(
+    mut udp_reader: UdpReader<S>,
+) -> Result<QueryFull, MspErr> {
+    let mut kv = read_kv_section(&mut udp_reader)?;
 
-    // Drop meaningless byte padding
-    udp_reader.set_current_idx_forward(11);
+    // Servers may reorder the keys or add their own, so look the values up by key.
+    let mut value = |key: &str| match kv.iter().position(|(k, _)| k == key) {
+        Some(idx) => kv.swap_remove(idx).1,
+        None => "".into(),
+    };
 
-    // Plugin format: [SERVER_MOD_NAME[: PLUGIN_NAME(; PLUGIN_NAME...)]]
-    //
-    // TODO So far, there have been no cases of multiple mod plugins.
-    // Therefore, for now, we are considering a single mod plugin.
-    let resolve_plugin = |plugin_str: String| -> Result<Vec<ModPlugin>, MspErr> {
-        if plugin_str.len() == 0 {
-            return Ok(vec![]);
+    Ok(QueryFull {
+        hostname: value("hostname"),
+        gametype: value("gametype"),
+        game_id: value("game_id"),
+        version: value("version"),
+        plugins: parse_plugins(&value("plugins")),
+        map: value("map"),
+        online_players: value("numplayers"),
+        maxplayers: value("maxplayers"),
+        port: value("hostport"),
+        ip: value("hostip"),
+        players: read_players(&mut udp_reader)?,
+    })
+}
+
+/// Parse the `plugins` value of full stat response.
+///
+/// Format: `[MOD_NAME[: PLUGIN_NAME(; PLUGIN_NAME...)]]`, repeated with `: ` for servers
+/// running several mod frameworks, e.g. `MOD1: p1; p2: MOD2: p3`.
+fn parse_plugins(plugin_str: &str) -> Vec<ModPlugin> {
+    if plugin_str.trim().is_empty() {
+        return vec![];
+    }
+
+    let segments = plugin_str.split(':').map(|x| x.trim()).collect::<Vec<_>>();
+
+    // Segments alternate between a mod name and its plugins, the last mod may have none.
+    segments
+        .chunks(2)
+        .map(|chunk| ModPlugin {
+            mod_name: chunk[0].into(),
+            plugins: chunk.get(1).map_or(vec![], |plugins| {
+                plugins
+                    .split(';')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.into())
+                    .collect()
+            }),
+        })
+        .collect()
+}
+
+/// Parse only the player section of full stat response.
+fn process_query_players_reader<S: UdpPeek>(
+    mut udp_reader: UdpReader<S>,
+) -> Result<Vec<String>, MspErr> {
+    read_kv_section(&mut udp_reader)?;
+    read_players(&mut udp_reader)
+}
+
+/// Read the KV section of the full stat response, after the type and session ID.
+fn read_kv_section<S: UdpPeek>(
+    udp_reader: &mut UdpReader<S>,
+) -> Result<Vec<(String, String)>, MspErr> {
+    // Drop `splitnum` padding if any, see [FullStatFragments].
+    if udp_reader.read_bufs(SPLITNUM.len()).ok().as_deref() == Some(SPLITNUM) {
+        udp_reader.set_current_idx_forward(FULL_STAT_HEADER_LEN - 5 - SPLITNUM.len());
+    } else {
+        udp_reader.set_current_idx(5);
+    }
+
+    // The KV section ends with an empty key.
+    let mut kv = Vec::<(String, String)>::new();
+
+    loop {
+        let key = udp_reader.read_nt_str()?;
+
+        if key.is_empty() {
+            break;
         }
 
-        let mut result = Vec::new();
-        let plugin_collection = plugin_str.split(":").map(|x| x.trim()).collect::<Vec<_>>();
-
-        match plugin_collection.len() {
-            2 => {
-                result.push(ModPlugin {
-                    mod_name: plugin_collection[0].into(),
-                    plugins: plugin_collection[1]
-                        .split(";")
-                        .map(|x| x.trim().into())
-                        .collect::<Vec<_>>(),
-                });
-            }
-            1 => {
-                result.push(ModPlugin {
-                    mod_name: plugin_collection[0].into(),
-                    plugins: vec![],
-                });
-            }
-            _ => {
-                return Err(MspErr::DataErr("Multiple mod plugin formats have been detected. Please submit the server address to the issues section to help us improve.".into()));
-            }
+        kv.push((key, udp_reader.read_nt_str()?));
+    }
+
+    Ok(kv)
+}
+
+/// Read the player section following the KV section of the full stat response.
+fn read_players<S: UdpPeek>(udp_reader: &mut UdpReader<S>) -> Result<Vec<String>, MspErr> {
+    // Some servers leave out the player section.
+    if udp_reader.remaining()? == 0 {
+        return Ok(vec![]);
+    }
+
+    // Skip the padding before the player names, `\x01player_\0\0` on vanilla servers.
+    while !udp_reader.read_nt_str()?.ends_with(PLAYER_SECTION_NAME) {}
+
+    if udp_reader.read(false)? == 0x00 {
+        udp_reader.read(true)?;
+    }
+
+    udp_reader.read_nt_str_group()
+}
+
+#[cfg(test)]
+mod query_test {
+    use super::*;
+
+    #[test]
+    fn test_query_full_round_trip() {
+        let query_full = QueryFull {
+            hostname: "A Minecraft Server".into(),
+            gametype: "SMP".into(),
+            game_id: "MINECRAFT".into(),
+            version: "1.20.1".into(),
+            plugins: vec![ModPlugin {
+                mod_name: "Paper on 1.20.1".into(),
+                plugins: vec!["WorldEdit 7.2.15".into()],
+            }],
+            map: "world".into(),
+            online_players: "1".into(),
+            maxplayers: "20".into(),
+            port: "25565".into(),
+            ip: "127.0.0.1".into(),
+            players: vec!["Notch".into()],
         };
+        let json = serde_json::to_string(&query_full).unwrap();
+        let round_trip = serde_json::from_str::<QueryFull>(&json).unwrap();
 
-        Ok(result)
-    };
+        assert_eq!(round_trip.to_string(), query_full.to_string());
+        assert_eq!(round_trip.online_players().unwrap(), 1);
+        assert_eq!(round_trip.max_players().unwrap(), 20);
+    }
 
-    Ok(QueryFull {
-        hostname: udp_reader.read_nt_kv()?.1,
-        gametype: udp_reader.read_nt_kv()?.1,
-        game_id: udp_reader.read_nt_kv()?.1,
-        version: udp_reader.read_nt_kv()?.1,
-        plugins: resolve_plugin(udp_reader.read_nt_kv()?.1)?,
-        map: udp_reader.read_nt_kv()?.1,
-        online_players: udp_reader.read_nt_kv()?.1,
-        maxplayers: udp_reader.read_nt_kv()?.1,
-        port: udp_reader.read_nt_kv()?.1,
-        ip: udp_reader.read_nt_kv()?.1,
-        players: {
-            // Because there are two null-terminated tokens at the end of the KV section,
-            // only one was consumed previously.
-            udp_reader.set_current_idx_forward(10 + 1);
-            udp_reader.read_nt_str_group()?
-        },
-    })
+    #[test]
+    fn test_parse_plugins() {
+        let mod_plugin = |mod_name: &str, plugins: &[&str]| ModPlugin {
+            mod_name: mod_name.into(),
+            plugins: plugins.iter().map(|x| x.to_string()).collect(),
+        };
+
+        assert!(parse_plugins("").is_empty());
+        assert_eq!(
+            parse_plugins("Paper on 1.20.1"),
+            vec![mod_plugin("Paper on 1.20.1", &[])]
+        );
+        assert_eq!(
+            parse_plugins("Paper on 1.20.1: WorldEdit 7.2.15; Essentials"),
+            vec![mod_plugin(
+                "Paper on 1.20.1",
+                &["WorldEdit 7.2.15", "Essentials"]
+            )]
+        );
+        assert_eq!(
+            parse_plugins("MOD1: p1; p2: MOD2: p3"),
+            vec![
+                mod_plugin("MOD1", &["p1", "p2"]),
+                mod_plugin("MOD2", &["p3"])
+            ]
+        );
+        assert_eq!(
+            parse_plugins("MOD1: p1: MOD2"),
+            vec![mod_plugin("MOD1", &["p1"]), mod_plugin("MOD2", &[])]
+        );
+    }
+
+    #[test]
+    fn test_full_stat_fragments() {
+        let mut payload = Vec::new();
+
+        for (k, v) in [
+            ("hostname", "A Minecraft Server"),
+            ("gametype", "SMP"),
+            ("game_id", "MINECRAFT"),
+            ("version", "1.20.1"),
+            ("plugins", ""),
+            ("map", "world"),
+            ("numplayers", "150"),
+            ("maxplayers", "200"),
+            ("hostport", "25565"),
+            ("hostip", "127.0.0.1"),
+        ] {
+            payload.extend_from_slice(format!("{}\0{}\0", k, v).as_bytes());
+        }
+
+        payload.extend_from_slice(b"\0\x01player_\0\0");
+
+        for idx in 0..150 {
+            payload.extend_from_slice(format!("Player{}\0", idx).as_bytes());
+        }
+
+        payload.push(0x00);
+
+        let fragment = |idx: u8, bufs: &[u8]| {
+            [
+                &[0x00, 0x00, 0x00, 0x00, 0x01][..],
+                SPLITNUM,
+                &[idx, 0x00],
+                bufs,
+            ]
+            .concat()
+        };
+        let (first, second) = payload.split_at(payload.len() / 2);
+        let mut fragments = FullStatFragments::default();
+
+        // Fragments may arrive out of order.
+        assert!(fragments.push(&fragment(0x81, second)).unwrap().is_none());
+
+        let response = fragments.push(&fragment(0x00, first)).unwrap().unwrap();
+        let query_full =
+            process_query_full_reader(UdpReader::create_with_idx(response, 5)).unwrap();
+
+        assert_eq!(query_full.online_players().unwrap(), 150);
+        assert_eq!(query_full.players.len(), 150);
+        assert_eq!(query_full.players[149], "Player149");
+    }
+
+    #[test]
+    fn test_full_stat_kv_by_key() {
+        let response = [
+            &[0x00, 0x00, 0x00, 0x00, 0x01][..],
+            SPLITNUM,
+            &[0x80, 0x00],
+            b"version\x001.20.1\0hostname\0Modded\0motd_extra\0x\0numplayers\x001\0\0",
+            b"\x01player_\0\0Steve\0\0",
+        ]
+        .concat();
+        let query_full =
+            process_query_full_reader(UdpReader::create_with_idx(response.clone(), 5)).unwrap();
+
+        assert_eq!(query_full.hostname, "Modded");
+        assert_eq!(query_full.version, "1.20.1");
+        assert_eq!(query_full.online_players().unwrap(), 1);
+        assert_eq!(query_full.map, "");
+        assert_eq!(query_full.players, vec!["Steve"]);
+        assert_eq!(
+            process_query_players_reader(UdpReader::create_with_idx(response.clone(), 5)).unwrap(),
+            vec!["Steve"]
+        );
+
+        // Without `splitnum` padding nor player section.
+        let response = b"\0\0\0\0\x01hostname\0A\0\0".to_vec();
+        let query_full =
+            process_query_full_reader(UdpReader::create_with_idx(response, 5)).unwrap();
+
+        assert_eq!(query_full.hostname, "A");
+        assert!(query_full.players.is_empty());
+    }
+
+    #[test]
+    fn test_query_with_token() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut conf = Conf::create_with_port("127.0.0.1", server.local_addr().unwrap().port());
+
+        conf.socket_conf.rep_udp_port = 0;
+        conf.socket_conf.read_time_out = Some(std::time::Duration::from_secs(3));
+
+        let handle = std::thread::spawn(move || {
+            let mut bufs = [0u8; 64];
+            let (_, client_addr) = server.recv_from(&mut bufs).unwrap();
+
+            server
+                .send_to(b"\x09\x00\x00\x00\x019513307\x00", client_addr)
+                .unwrap();
+
+            let (len, addr) = server.recv_from(&mut bufs).unwrap();
+
+            // The stat request is sent from the handshake address, with the token.
+            assert_eq!(addr, client_addr);
+            assert_eq!(&bufs[7..len], &[0x00, 0x91, 0x29, 0x5B]);
+            server
+                .send_to(
+                    b"\x00\x00\x00\x00\x01A Server\x00SMP\x00world\x002\x0020\x00\xDD\x63127.0.0.1\x00",
+                    addr,
+                )
+                .unwrap();
+        });
+        let token = conf.query_handshake().unwrap();
+        let query_basic = conf.query_basic_with_token(&token).unwrap();
+
+        handle.join().unwrap();
+        assert_eq!(token.token, 9513307);
+        assert_eq!(query_basic.motd, "A Server");
+        assert_eq!(query_basic.max_players().unwrap(), 20);
+    }
+
+    #[test]
+    fn test_challenge_token() {
+        // Handshake response of a vanilla server, zero padded as in the receive buffer.
+        let bufs = *b"\x09\x00\x00\x00\x01\x39\x35\x31\x33\x33\x30\x37\x00\x00\x00\x00\x00";
+        let token = check_handshake_response(&bufs).unwrap();
+
+        assert_eq!(token, 9513307);
+        assert_eq!(
+            build_stat_request_packet(token, false),
+            vec![0xFE, 0xFD, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x91, 0x29, 0x5B]
+        );
+
+        // Negative and unsigned tokens are sent back whole.
+        let bufs = *b"\x09\x00\x00\x00\x01-1384372393\x00";
+        let token = check_handshake_response(&bufs).unwrap();
+
+        assert_eq!(
+            &build_stat_request_packet(token, false)[7..],
+            &[0xAD, 0x7C, 0x27, 0x57]
+        );
+
+        let bufs = *b"\x09\x00\x00\x00\x012910594903\x00\x00";
+        let token = check_handshake_response(&bufs).unwrap();
+
+        assert_eq!(
+            &build_stat_request_packet(token, false)[7..],
+            &[0xAD, 0x7C, 0x27, 0x57]
+        );
+
+        // The session ID is echoed masked.
+        assert!(check_stat_response(&[0x00, 0x10, 0x20, 0x30, 0x41]).is_ok());
+        assert!(check_stat_response(&[0x00, 0x00, 0x00, 0x00, 0x02]).is_err());
+    }
+
+    #[test]
+    fn test_handshake_response_checks() {
+        assert_eq!(
+            build_handshake_packet(),
+            vec![0xFE, 0xFD, 0x09, 0x00, 0x00, 0x00, 0x01]
+        );
+
+        // A token of 1 is not mistaken for the session ID.
+        assert_eq!(
+            check_handshake_response(b"\x09\x00\x00\x00\x011\x00").unwrap(),
+            1
+        );
+        assert_eq!(
+            check_handshake_response(b"\x09\xF0\x00\x00\x11123\x00").unwrap(),
+            123
+        );
+
+        for bufs in [
+            // Session ID mismatch.
+            &b"\x09\x00\x00\x00\x02123\x00"[..],
+            // Stat response type.
+            b"\x00\x00\x00\x00\x01123\x00",
+            // No token.
+            b"\x09\x00\x00\x00\x01\x00",
+        ] {
+            assert!(check_handshake_response(bufs).is_err());
+        }
+
+        assert!(check_stat_response(&[0x09, 0x00, 0x00, 0x00, 0x01]).is_err());
+        assert!(check_stat_response(&[0x00, 0x00]).is_err());
+    }
 }
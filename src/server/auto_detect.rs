@@ -0,0 +1,283 @@
+use super::{
+    get_bedrock_server_status, get_beta_legacy_server_status, get_legacy_server_status,
+    get_netty_server_status, get_server_status, BedrockServer, LegacyBetaServer, LegacyServer,
+    NettyServer, Server,
+};
+use crate::{
+    conf::Conf,
+    query::query_basic_status,
+    share::{display_json, ToJson},
+    MspErr, SocketConf, BEDROCK_DEFAULT_PORT,
+};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Timeout of each socket operation in [probe_protocols], unless the configured one is shorter.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Server info returned by [Conf::get_status_auto], tagged with the protocol that succeeded.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "server")]
+// Returned once per call, boxing [Server] is not worth the API change.
+#[allow(clippy::large_enum_variant)]
+pub enum ServerKind {
+    /// Modern Java Edition server, see [Conf::get_server_status].
+    Regular(Server),
+    /// Java Edition server 1.6, see [Conf::get_netty_server_status].
+    Netty(NettyServer),
+    /// Java Edition server 1.4 to 1.5, see [Conf::get_legacy_server_status].
+    Legacy(LegacyServer),
+    /// Java Edition server Beta 1.8 to 1.3, see [Conf::get_beta_legacy_server_status].
+    LegacyBeta(LegacyBetaServer),
+    /// Bedrock Edition server, see [Conf::get_bedrock_server_status].
+    Bedrock(BedrockServer),
+}
+
+impl std::fmt::Display for ServerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_json(self, f)
+    }
+}
+
+impl ToJson for ServerKind {}
+
+/// Protocols a server answers to, returned by [Conf::probe_protocols].
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProtocolSupport {
+    /// [Conf::get_server_status] succeeded.
+    pub modern: bool,
+    /// [Conf::get_netty_server_status] succeeded.
+    pub netty: bool,
+    /// [Conf::get_legacy_server_status] succeeded.
+    pub legacy: bool,
+    /// [Conf::get_beta_legacy_server_status] succeeded.
+    pub legacy_beta: bool,
+    /// [Conf::query] succeeded, i.e. `enable-query` is on.
+    pub query: bool,
+    /// [Conf::get_bedrock_server_status] succeeded.
+    pub bedrock: bool,
+}
+
+/// What to do after a protocol attempt failed.
+enum Fallback {
+    /// The server speaks another protocol, try the next one.
+    Next,
+    /// Nothing listens on TCP, skip to Bedrock.
+    Bedrock,
+    /// Genuine network failure, give up.
+    Abort,
+}
+
+fn fallback_of(err: &MspErr) -> Fallback {
+    match err {
+        // Servers usually close the connection or stop responding
+        // when they do not understand the request.
+        MspErr::DataErr(_) | MspErr::InternalErr(_) | MspErr::JsonErr(_) | MspErr::Timeout(_) => {
+            Fallback::Next
+        }
+        MspErr::IoErr(err) => match err.kind() {
+            std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::InvalidData => Fallback::Next,
+            _ => Fallback::Abort,
+        },
+        MspErr::ConnRefused(_) => Fallback::Bedrock,
+        MspErr::NoImpl(_) | MspErr::DnsErr(_) => Fallback::Abort,
+    }
+}
+
+/// Probe the server with each protocol in order, see [Conf::get_status_auto].
+pub fn get_status_auto(conf: &Conf) -> Result<ServerKind, MspErr> {
+    let try_bedrock = || get_bedrock_server_status(conf).map(ServerKind::Bedrock);
+
+    if conf.port == BEDROCK_DEFAULT_PORT && conf.proxy.is_none() {
+        match try_bedrock() {
+            Ok(server) => return Ok(server),
+            Err(err) if matches!(fallback_of(&err), Fallback::Abort) => return Err(err),
+            Err(_) => {}
+        }
+    }
+
+    let attempts: [&dyn Fn() -> Result<ServerKind, MspErr>; 4] = [
+        &|| get_server_status(conf).map(ServerKind::Regular),
+        &|| get_netty_server_status(conf).map(ServerKind::Netty),
+        &|| get_legacy_server_status(conf).map(ServerKind::Legacy),
+        &|| get_beta_legacy_server_status(conf).map(ServerKind::LegacyBeta),
+    ];
+    let mut last_err = None;
+
+    for attempt in attempts {
+        match attempt() {
+            Ok(server) => return Ok(server),
+            Err(err) => match fallback_of(&err) {
+                Fallback::Next => last_err = Some(err),
+                Fallback::Bedrock => {
+                    last_err = Some(err);
+                    break;
+                }
+                Fallback::Abort => return Err(err),
+            },
+        }
+    }
+
+    match last_err {
+        // Bedrock has been tried already, or cannot be tried through proxy.
+        Some(err) if conf.port == BEDROCK_DEFAULT_PORT || conf.proxy.is_some() => Err(err),
+        _ => try_bedrock(),
+    }
+}
+
+/// Async version of [get_status_auto].
+#[cfg(feature = "tokio")]
+pub async fn get_status_auto_async(conf: &Conf) -> Result<ServerKind, MspErr> {
+    use super::{
+        get_bedrock_server_status_async, get_beta_legacy_server_status_async,
+        get_legacy_server_status_async, get_netty_server_status_async, get_server_status_async,
+    };
+
+    if conf.port == BEDROCK_DEFAULT_PORT && conf.proxy.is_none() {
+        match get_bedrock_server_status_async(conf).await {
+            Ok(server) => return Ok(ServerKind::Bedrock(server)),
+            Err(err) if matches!(fallback_of(&err), Fallback::Abort) => return Err(err),
+            Err(_) => {}
+        }
+    }
+
+    let mut last_err = None;
+
+    for idx in 0..4 {
+        let result = match idx {
+            0 => get_server_status_async(conf).await.map(ServerKind::Regular),
+            1 => get_netty_server_status_async(conf)
+                .await
+                .map(ServerKind::Netty),
+            2 => get_legacy_server_status_async(conf)
+                .await
+                .map(ServerKind::Legacy),
+            _ => get_beta_legacy_server_status_async(conf)
+                .await
+                .map(ServerKind::LegacyBeta),
+        };
+
+        match result {
+            Ok(server) => return Ok(server),
+            Err(err) => match fallback_of(&err) {
+                Fallback::Next => last_err = Some(err),
+                Fallback::Bedrock => {
+                    last_err = Some(err);
+                    break;
+                }
+                Fallback::Abort => return Err(err),
+            },
+        }
+    }
+
+    match last_err {
+        Some(err) if conf.port == BEDROCK_DEFAULT_PORT || conf.proxy.is_some() => Err(err),
+        _ => get_bedrock_server_status_async(conf)
+            .await
+            .map(ServerKind::Bedrock),
+    }
+}
+
+/// Configuration of each attempt in [probe_protocols]: timeouts capped by [PROBE_TIMEOUT]
+/// and no retries.
+fn probe_conf(conf: &Conf) -> Conf {
+    let cap = |timeout: Option<Duration>| {
+        Some(timeout.map_or(PROBE_TIMEOUT, |timeout| timeout.min(PROBE_TIMEOUT)))
+    };
+
+    Conf {
+        socket_conf: SocketConf {
+            read_time_out: cap(conf.socket_conf.read_time_out),
+            write_timeout: cap(conf.socket_conf.write_timeout),
+            connect_timeout: cap(conf.socket_conf.connect_timeout),
+            max_retries: 0,
+            ..conf.socket_conf.clone()
+        },
+        ..conf.clone()
+    }
+}
+
+/// Try every protocol against the server, see [Conf::probe_protocols].
+pub fn probe_protocols(conf: &Conf) -> ProtocolSupport {
+    let conf = probe_conf(conf);
+
+    ProtocolSupport {
+        modern: get_server_status(&conf).is_ok(),
+        netty: get_netty_server_status(&conf).is_ok(),
+        legacy: get_legacy_server_status(&conf).is_ok(),
+        legacy_beta: get_beta_legacy_server_status(&conf).is_ok(),
+        query: query_basic_status(&conf).is_ok(),
+        bedrock: get_bedrock_server_status(&conf).is_ok(),
+    }
+}
+
+/// Async version of [probe_protocols].
+#[cfg(feature = "tokio")]
+pub async fn probe_protocols_async(conf: &Conf) -> ProtocolSupport {
+    use super::{
+        get_bedrock_server_status_async, get_beta_legacy_server_status_async,
+        get_legacy_server_status_async, get_netty_server_status_async, get_server_status_async,
+    };
+    use crate::query::query_basic_status_async;
+
+    let conf = probe_conf(conf);
+
+    ProtocolSupport {
+        modern: get_server_status_async(&conf).await.is_ok(),
+        netty: get_netty_server_status_async(&conf).await.is_ok(),
+        legacy: get_legacy_server_status_async(&conf).await.is_ok(),
+        legacy_beta: get_beta_legacy_server_status_async(&conf).await.is_ok(),
+        query: query_basic_status_async(&conf).await.is_ok(),
+        bedrock: get_bedrock_server_status_async(&conf).await.is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod auto_detect_test {
+    use super::*;
+
+    #[test]
+    fn test_fallback_of() {
+        let io_err = |kind: std::io::ErrorKind| MspErr::from(std::io::Error::from(kind));
+
+        assert!(matches!(
+            fallback_of(&MspErr::DataErr("".into())),
+            Fallback::Next
+        ));
+        assert!(matches!(
+            fallback_of(&io_err(std::io::ErrorKind::UnexpectedEof)),
+            Fallback::Next
+        ));
+        assert!(matches!(
+            fallback_of(&io_err(std::io::ErrorKind::ConnectionRefused)),
+            Fallback::Bedrock
+        ));
+        assert!(matches!(
+            fallback_of(&io_err(std::io::ErrorKind::HostUnreachable)),
+            Fallback::Abort
+        ));
+    }
+
+    #[test]
+    fn test_probe_conf() {
+        let mut conf = Conf::create("www.example.com");
+
+        conf.socket_conf.read_time_out = Some(Duration::from_millis(300));
+        conf.socket_conf.write_timeout = Some(Duration::from_secs(30));
+        conf.socket_conf.max_retries = 3;
+
+        let probe_conf = probe_conf(&conf);
+
+        assert_eq!(
+            probe_conf.socket_conf.read_time_out,
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(probe_conf.socket_conf.write_timeout, Some(PROBE_TIMEOUT));
+        assert_eq!(probe_conf.socket_conf.connect_timeout, Some(PROBE_TIMEOUT));
+        assert_eq!(probe_conf.socket_conf.max_retries, 0);
+        assert_eq!(probe_conf.host, conf.host);
+    }
+}
@@ -2,9 +2,19 @@ use serde::Serialize;
 
 use crate::{
     conf::Conf,
-    share::{create_udp_socket, UdpReader},
-    MspErr,
+    share::{
+        check_datagram_sent, check_udp_proxy, create_udp_socket_for, display_json,
+        resolve_conf_addrs, trim_trailing_nul, ToJson, UdpPeek, UdpReader, UDP_MAX_DATAGRAM_SIZE,
+    },
+    Description, IpPreference, MspErr,
 };
+use std::{
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Packet ID of the [unconnected pong](https://wiki.vg/Raknet_Protocol#Unconnected_Pong).
+pub const UNCONNECTED_PONG_ID: u8 = 0x1C;
 
 const MAGIC_BYTES: &[u8] = &[
     0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE, 0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78,
@@ -21,7 +31,7 @@ const MAGIC_BYTES: &[u8] = &[
 /// Result:
 ///
 /// <img src="https://wiki.vg/images/b/bb/Server_ID_String_Example.png" alt="Server ID String Example.png" />
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct BedrockServer {
     /// MCPE or MCEE(Education Edition) for Education Edition
     pub edition: String,
@@ -35,34 +45,203 @@ pub struct BedrockServer {
     pub online_players: i32,
     /// Max players.
     pub max_players: i32,
-    /// Server unique id.
+    /// Server unique id, as sent in the server ID string.
     pub server_id: String,
+    /// Server GUID from the unconnected pong packet, stays the same across IP changes.
+    pub server_guid: u64,
     /// MOTD line 2 for downstream display.
     pub motd_line_2: String,
     /// Game mode.
     pub game_mode: String,
-    /// Game mode id.
+    /// Game mode id, see [BedrockServer::game_mode_enum].
     pub game_mode_id: u8,
     /// Ports required to connect to the server using IPv4.
+    ///
+    /// Falls back to [BedrockServer::connected_port] if the server does not report it.
     pub port_ipv4: u16,
     /// Ports required to connect to the server using IPv6.
     pub port_ipv6: u16,
+    /// Port the ping was sent to, may differ from the advertised [BedrockServer::port_ipv4]
+    /// for servers behind a port mapper.
+    pub connected_port: u16,
 }
 
 impl std::fmt::Display for BedrockServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
+    }
+}
+
+impl ToJson for BedrockServer {}
+
+impl BedrockServer {
+    /// Both MOTD lines (joined with a newline) parsed into a structured [Description],
+    /// see [Description::from_legacy].
+    pub fn description(&self) -> Description {
+        Description::from_legacy(&format!("{}\n{}", self.motd_line_1, self.motd_line_2))
+    }
+
+    /// Both MOTD lines without formatting codes, joined with a newline.
+    pub fn motd_plain(&self) -> String {
+        self.description().to_plain()
+    }
+
+    /// [BedrockServer::game_mode_id] mapped into [GameMode].
+    pub fn game_mode_enum(&self) -> GameMode {
+        GameMode::from(self.game_mode_id)
+    }
+}
+
+/// Bedrock Edition game mode.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Game mode id 0.
+    Survival,
+    /// Game mode id 1.
+    Creative,
+    /// Game mode id 2.
+    Adventure,
+    /// Game mode id 6.
+    Spectator,
+    /// Game mode id not known by this crate.
+    Unknown(u8),
+}
+
+impl From<u8> for GameMode {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => GameMode::Survival,
+            1 => GameMode::Creative,
+            2 => GameMode::Adventure,
+            6 => GameMode::Spectator,
+            id => GameMode::Unknown(id),
+        }
     }
 }
 
 pub fn get_bedrock_server_status(conf: &Conf) -> Result<BedrockServer, MspErr> {
-    let socket = create_udp_socket(&conf.socket_conf)?;
+    check_udp_proxy(conf)?;
+
+    // IPv4 is preferred, see [SocketConf::rep_udp_ipv6](crate::SocketConf::rep_udp_ipv6).
+    let addr = resolve_conf_addrs(conf, IpPreference::Ipv4)?[0];
+    let socket = create_udp_socket_for(&conf.socket_conf, &addr)?;
+    let attempts = conf.socket_conf.bedrock_ping_attempts.max(1);
+
+    for attempt in 1..=attempts {
+        let ping = build_unconnected_ping_packet();
+
+        check_datagram_sent(socket.send_to(&ping, addr)?, &ping)?;
+
+        match recv_unconnected_pong(&socket, ping_wait(conf, attempt == attempts)) {
+            Ok(bufs) => {
+                return process_bedrock_server_reader(UdpReader::create_with_idx(bufs, 0), conf)
+            }
+            Err(MspErr::Timeout(_)) if attempt < attempts => {
+                log::debug!("No unconnected pong after ping {}, resending.", attempt);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    unreachable!("The last ping attempt always returns.")
+}
+
+/// Async version of [get_bedrock_server_status].
+#[cfg(feature = "tokio")]
+pub async fn get_bedrock_server_status_async(conf: &Conf) -> Result<BedrockServer, MspErr> {
+    use crate::share::{create_udp_socket_for_async, resolve_conf_addrs_async, timeout_async};
+
+    check_udp_proxy(conf)?;
+
+    let addr = resolve_conf_addrs_async(conf, IpPreference::Ipv4).await?[0];
+    let socket = create_udp_socket_for_async(&conf.socket_conf, &addr).await?;
+    let attempts = conf.socket_conf.bedrock_ping_attempts.max(1);
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    for attempt in 1..=attempts {
+        let ping = build_unconnected_ping_packet();
+        let sent =
+            timeout_async(conf.socket_conf.write_timeout, socket.send_to(&ping, addr)).await?;
+
+        check_datagram_sent(sent, &ping)?;
+
+        // Skip datagrams other than the unconnected pong, see [recv_unconnected_pong].
+        let result = timeout_async(ping_wait(conf, attempt == attempts), async {
+            loop {
+                let len = socket.recv(&mut bufs).await?;
+
+                if bufs[..len].first() == Some(&UNCONNECTED_PONG_ID) {
+                    return Ok::<_, std::io::Error>(len);
+                }
+            }
+        })
+        .await;
+
+        match result {
+            Ok(len) => {
+                bufs.truncate(len);
+
+                return process_bedrock_server_reader(UdpReader::create_with_idx(bufs, 0), conf);
+            }
+            Err(MspErr::Timeout(_)) if attempt < attempts => {
+                log::debug!("No unconnected pong after ping {}, resending.", attempt);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    unreachable!("The last ping attempt always returns.")
+}
+
+/// How long to wait for the pong before resending the ping, the last attempt
+/// waits for the whole [SocketConf::read_time_out](crate::SocketConf::read_time_out).
+fn ping_wait(conf: &Conf, last_attempt: bool) -> Option<Duration> {
+    let read_time_out = conf.socket_conf.read_time_out;
+
+    match last_attempt {
+        true => read_time_out,
+        false => Some(
+            read_time_out.map_or(conf.socket_conf.bedrock_ping_interval, |read_time_out| {
+                read_time_out.min(conf.socket_conf.bedrock_ping_interval)
+            }),
+        ),
+    }
+}
+
+/// Wait up to `wait` for an unconnected pong, skipping any other datagram
+/// that reaches the socket in the meantime.
+fn recv_unconnected_pong(socket: &UdpSocket, wait: Option<Duration>) -> Result<Vec<u8>, MspErr> {
+    let deadline = wait.map(|wait| Instant::now() + wait);
+    let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+
+    loop {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+            }
+
+            socket.set_read_timeout(Some(remaining))?;
+        } else {
+            socket.set_read_timeout(None)?;
+        }
+
+        let len = socket.recv(&mut bufs)?;
+
+        if bufs[..len].first() == Some(&UNCONNECTED_PONG_ID) {
+            bufs.truncate(len);
+
+            return Ok(bufs);
+        }
 
-    let packet = [
+        log::debug!("Skip datagram that is not an unconnected pong.");
+    }
+}
+
+/// Build [unconnected ping](https://wiki.vg/Raknet_Protocol#Unconnected_Ping) packet buffer.
+pub fn build_unconnected_ping_packet() -> Vec<u8> {
+    [
         // Packet ID
         &[0x01],
         // Time
@@ -70,14 +249,24 @@ pub fn get_bedrock_server_status(conf: &Conf) -> Result<BedrockServer, MspErr> {
         // MAGIC
         MAGIC_BYTES,
     ]
-    .concat();
+    .concat()
+}
 
-    socket.send_to(packet.as_slice(), conf)?;
+/// Parse an unconnected pong not sent in reply to [get_bedrock_server_status],
+/// e.g. during LAN discovery, with `addr` being the sender of the pong.
+pub fn process_unconnected_pong(bufs: Vec<u8>, addr: SocketAddr) -> Result<BedrockServer, MspErr> {
+    let conf = Conf::create_with_port(&addr.ip().to_string(), addr.port());
 
-    let mut udp_reader = UdpReader::create_with_idx(socket, 0);
+    process_bedrock_server_reader(UdpReader::create_with_idx(bufs, 0), &conf)
+}
 
+/// Parse the unconnected pong response into [BedrockServer].
+fn process_bedrock_server_reader<S: UdpPeek>(
+    mut udp_reader: UdpReader<S>,
+    conf: &Conf,
+) -> Result<BedrockServer, MspErr> {
     match udp_reader.read_bufs(1)?.get(0) {
-        Some(&first_buf) if first_buf != 0x1C => {
+        Some(&first_buf) if first_buf != UNCONNECTED_PONG_ID => {
             return Err(MspErr::DataErr(format!(
                 "Packet response excepted start with: 0x1C, but got: 0x{:02X}",
                 first_buf
@@ -89,48 +278,196 @@ pub fn get_bedrock_server_status(conf: &Conf) -> Result<BedrockServer, MspErr> {
     // Drop time data(8 bytes)
     udp_reader.set_current_idx_forward(8);
 
-    let _server_guid = udp_reader.read_bufs(8)?;
-    let _magic_bytes = udp_reader.read_bufs(16)?;
+    let server_guid = match udp_reader.read_bufs(8)?.try_into() {
+        Ok(guid) => u64::from_be_bytes(guid),
+        Err(_) => {
+            return Err(MspErr::DataErr("Cannot convert to u64.".into()));
+        }
+    };
+    // A datagram of another protocol, or corrupted on the way.
+    if udp_reader.read_bufs(MAGIC_BYTES.len())? != MAGIC_BYTES {
+        return Err(MspErr::DataErr(
+            "Unconnected pong has invalid offline message magic bytes.".into(),
+        ));
+    }
+
     let server_info_len = match udp_reader.read_bufs(2)?.try_into() {
         Ok(len) => u16::from_be_bytes(len) as usize,
         Err(_) => {
             return Err(MspErr::DataErr("Cannot convert to u16.".into()));
         }
     };
+    let remaining = udp_reader.remaining()?;
+
+    // The length comes from the server, do not trust it beyond the received datagram.
+    //
+    // RakNet does not split unconnected pongs, and IP fragments are reassembled by the OS
+    // into a datagram of up to [UDP_MAX_DATAGRAM_SIZE], so a shorter server info means the
+    // response was truncated on the way, e.g. by a middlebox.
+    if server_info_len > remaining {
+        return Err(MspErr::DataErr(format!(
+            "Truncated unconnected pong, server info length {} exceeds the {} bytes left in the response.",
+            server_info_len, remaining
+        )));
+    }
+
     let server_info_buf = udp_reader.read_bufs(server_info_len)?;
-    let server_info = String::from_utf8_lossy(server_info_buf.as_slice());
+    // Some servers pad the server ID string with NULs.
+    let server_info = String::from_utf8_lossy(trim_trailing_nul(&server_info_buf));
 
-    println!("{:?}", server_info);
+    log::debug!("Bedrock server info: {:?}", server_info);
 
-    let server_info_split = server_info.split(";").collect::<Vec<_>>();
+    process_server_info(&server_info, server_guid, conf)
+}
 
-    if server_info_split.len() < 10 {
+/// Parse the semicolon separated server ID string.
+///
+/// Only the first 6 fields are required, forks like PocketMine and Nukkit may leave
+/// out the trailing ones, which then fall back to their defaults.
+fn process_server_info(
+    server_info: &str,
+    server_guid: u64,
+    conf: &Conf,
+) -> Result<BedrockServer, MspErr> {
+    let fields = server_info.split(';').collect::<Vec<_>>();
+
+    if fields.len() < 6 {
         return Err(MspErr::DataErr(format!(
-            "Expected return at least 10 parts of server information, but {}  were obtained.",
-            server_info_split.len()
+            "Expected return at least 6 parts of server information, but {} were obtained.",
+            fields.len()
         )));
     }
 
+    let text = |idx: usize| fields.get(idx).map_or("", |field| field).to_string();
+
     Ok(BedrockServer {
-        edition: server_info_split[0].into(),
-        motd_line_1: server_info_split[1].into(),
-        protocol_version: server_info_split[2].parse()?,
-        version_name: server_info_split[3].into(),
-        online_players: server_info_split[4].parse()?,
-        max_players: server_info_split[5].parse()?,
-        server_id: server_info_split[6].into(),
-        motd_line_2: server_info_split[7].into(),
-        game_mode: server_info_split[8].into(),
-        game_mode_id: server_info_split[9].parse()?,
-        port_ipv4: if let Some(&p4) = server_info_split.get(10) {
-            p4.parse()?
-        } else {
-            conf.port
-        },
-        port_ipv6: if let Some(&p6) = server_info_split.get(11) {
-            p6.parse()?
-        } else {
-            0
-        },
+        edition: text(0),
+        motd_line_1: text(1),
+        protocol_version: parse_field(&fields, 2, "protocol_version")?.unwrap_or_default(),
+        version_name: text(3),
+        online_players: parse_field(&fields, 4, "online_players")?.unwrap_or_default(),
+        max_players: parse_field(&fields, 5, "max_players")?.unwrap_or_default(),
+        server_id: text(6),
+        server_guid,
+        motd_line_2: text(7),
+        game_mode: text(8),
+        game_mode_id: parse_field(&fields, 9, "game_mode_id")?.unwrap_or_default(),
+        port_ipv4: parse_field(&fields, 10, "port_ipv4")?.unwrap_or(conf.port),
+        port_ipv6: parse_field(&fields, 11, "port_ipv6")?.unwrap_or_default(),
+        connected_port: conf.port,
     })
 }
+
+/// Parse the field at `idx`, `None` if it is missing or empty.
+fn parse_field<T: std::str::FromStr>(
+    fields: &[&str],
+    idx: usize,
+    name: &str,
+) -> Result<Option<T>, MspErr>
+where
+    T::Err: std::fmt::Display,
+{
+    match fields.get(idx).map(|field| field.trim()) {
+        None | Some("") => Ok(None),
+        Some(field) => field.parse().map(Some).map_err(|err| {
+            MspErr::DataErr(format!(
+                "Cannot parse Bedrock server info field {}(index {}) from {:?}: {}",
+                name, idx, field, err
+            ))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod bedrock_server_test {
+    use super::*;
+
+    #[test]
+    fn test_process_server_info() {
+        let conf = Conf::create_with_port("localhost", 19132);
+        let server = process_server_info(
+            "MCPE;§aDedicated Server;622;1.20.40;0;10;13253860892328930865;Bedrock level;Survival;1;19134;19133;",
+            13253860892328930865,
+            &conf,
+        )
+        .unwrap();
+
+        assert_eq!(server.protocol_version, 622);
+        assert_eq!(server.game_mode_enum(), GameMode::Creative);
+        assert_eq!(server.port_ipv6, 19133);
+        assert_eq!((server.port_ipv4, server.connected_port), (19134, 19132));
+        assert_eq!(server.server_guid, 13253860892328930865);
+        assert_eq!(server.motd_plain(), "Dedicated Server\nBedrock level");
+
+        // PocketMine style, without the trailing fields.
+        let server =
+            process_server_info("MCPE;PocketMine-MP Server;589;1.20.0;2;20", 0, &conf).unwrap();
+
+        assert_eq!(server.online_players, 2);
+        assert_eq!(server.motd_line_2, "");
+        assert_eq!(server.port_ipv4, 19132);
+
+        match process_server_info("MCPE;Server;abc;1.20.0;2;20", 0, &conf) {
+            Err(MspErr::DataErr(msg)) => assert!(msg.contains("protocol_version(index 2)")),
+            result => panic!("Expected DataErr, but got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_ping_retransmission() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut conf = Conf::create_with_port("127.0.0.1", server.local_addr().unwrap().port());
+
+        conf.socket_conf.rep_udp_port = 0;
+        conf.socket_conf.read_time_out = Some(Duration::from_secs(3));
+        conf.socket_conf.bedrock_ping_interval = Duration::from_millis(100);
+
+        let handle = std::thread::spawn(move || {
+            let mut bufs = [0u8; 64];
+            // Drop the first ping as if it was lost.
+            let _ = server.recv_from(&mut bufs).unwrap();
+            let (_, addr) = server.recv_from(&mut bufs).unwrap();
+            let server_info = b"MCPE;Server;622;1.20.40;0;10";
+
+            server
+                .send_to(&build_pong(server_info, server_info.len() as u16), addr)
+                .unwrap();
+        });
+        let server = get_bedrock_server_status(&conf).unwrap();
+
+        handle.join().unwrap();
+        assert_eq!(server.server_guid, 42);
+        assert_eq!(server.max_players, 10);
+    }
+
+    #[test]
+    fn test_server_info_len_exceeds_datagram() {
+        let conf = Conf::create_with_port("localhost", 19132);
+        let pong = build_pong(b"MCPE;Server;622;1.20.40;0;10", u16::MAX);
+
+        match process_bedrock_server_reader(UdpReader::create_with_idx(pong, 0), &conf) {
+            Err(MspErr::DataErr(msg)) => assert!(msg.contains("65535")),
+            result => panic!("Expected DataErr, but got: {:?}", result),
+        }
+
+        let mut pong = build_pong(b"MCPE;Server;622;1.20.40;0;10", 28);
+
+        pong[17] ^= 0xFF;
+
+        match process_bedrock_server_reader(UdpReader::create_with_idx(pong, 0), &conf) {
+            Err(MspErr::DataErr(msg)) => assert!(msg.contains("magic")),
+            result => panic!("Expected DataErr, but got: {:?}", result),
+        }
+    }
+
+    fn build_pong(server_info: &[u8], server_info_len: u16) -> Vec<u8> {
+        let mut pong = vec![UNCONNECTED_PONG_ID];
+
+        pong.extend_from_slice(&[0u8; 8]);
+        pong.extend_from_slice(&42u64.to_be_bytes());
+        pong.extend_from_slice(MAGIC_BYTES);
+        pong.extend_from_slice(&server_info_len.to_be_bytes());
+        pong.extend_from_slice(server_info);
+        pong
+    }
+}
@@ -0,0 +1,335 @@
+use super::{ForgeChannel, ForgeData, ForgeMod};
+use crate::{varint::decode_varint_from_bufs, MspErr};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+
+/// `forgeData` as sent by the server, see [ForgeData].
+///
+/// `mods` and `channels` are kept untyped, so that a malformed entry only drops itself.
+#[derive(Deserialize)]
+pub(super) struct ForgeDataRepr {
+    #[serde(default)]
+    mods: Value,
+    #[serde(default)]
+    channels: Value,
+    #[serde(default)]
+    truncated: bool,
+    /// Mods and channels packed by Forge 1.18.2+, see [decode_optimized].
+    d: Option<String>,
+}
+
+impl From<ForgeDataRepr> for ForgeData {
+    fn from(repr: ForgeDataRepr) -> Self {
+        let mut warnings = Vec::new();
+        let mut legacy = ForgeData {
+            mods: parse_entries(repr.mods, "mods", &mut warnings),
+            channels: parse_entries(repr.channels, "channels", &mut warnings),
+            truncated: repr.truncated,
+            warnings,
+        };
+
+        match repr.d {
+            // The plain arrays take precedence when present.
+            Some(d) if legacy.mods.is_empty() => {
+                match decode_optimized(&d).and_then(|bufs| process_forge_bufs(&bufs)) {
+                    Ok(forge_data) => forge_data,
+                    Err(err) => {
+                        legacy
+                            .warnings
+                            .push(format!("Cannot decode packed forgeData: {}", err));
+                        legacy
+                    }
+                }
+            }
+            _ => legacy,
+        }
+    }
+}
+
+/// Parse the entries of a `forgeData` array one by one, skipping the malformed ones.
+fn parse_entries<T: DeserializeOwned>(
+    value: Value,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<T> {
+    let entries = match value {
+        Value::Null => return vec![],
+        Value::Array(entries) => entries,
+        value => {
+            warnings.push(format!("forgeData.{} is not an array: {}", name, value));
+            return vec![];
+        }
+    };
+
+    entries
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| match serde_json::from_value(entry) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                warnings.push(format!("Cannot parse forgeData.{}[{}]: {}", name, idx, err));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Read a flag sent as a boolean, a number or a string.
+pub(super) fn deserialize_flag<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Bool(flag) => Ok(flag),
+        Value::Number(num) => Ok(num.as_f64() != Some(0.0)),
+        Value::String(str) => match str.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" | "" => Ok(false),
+            _ => Err(serde::de::Error::custom(format!("invalid flag: {:?}", str))),
+        },
+        Value::Null => Ok(false),
+        value => Err(serde::de::Error::custom(format!("invalid flag: {}", value))),
+    }
+}
+
+/// Unpack the string Forge packs binary data into, 15 bits per character.
+///
+/// The first two characters hold the byte length, see `ServerStatusPing#decodeOptimized` of Forge.
+fn decode_optimized(d: &str) -> Result<Vec<u8>, MspErr> {
+    let mut chars = d.chars().map(|ch| ch as u32 & 0x7FFF);
+    let size = match (chars.next(), chars.next()) {
+        (Some(size0), Some(size1)) => (size0 | (size1 << 15)) as usize,
+        _ => return Ok(vec![]),
+    };
+    // Check the declared length against the data, instead of allocating up to 1 GiB for it.
+    let capacity = (chars.clone().count() * 15).div_ceil(8);
+
+    if size > capacity {
+        return Err(MspErr::DataErr(format!(
+            "Packed forgeData declares {} bytes, but only carries {}",
+            size, capacity
+        )));
+    }
+
+    let mut bufs = Vec::with_capacity(size);
+    let mut buffer = 0u32;
+    let mut bits_in_buf = 0;
+
+    for ch in chars {
+        while bits_in_buf >= 8 {
+            bufs.push(buffer as u8);
+            buffer >>= 8;
+            bits_in_buf -= 8;
+        }
+
+        buffer |= ch << bits_in_buf;
+        bits_in_buf += 15;
+    }
+
+    while bufs.len() < size && bits_in_buf > 0 {
+        bufs.push(buffer as u8);
+        buffer >>= 8;
+        bits_in_buf -= 8.min(bits_in_buf);
+    }
+
+    bufs.truncate(size);
+
+    Ok(bufs)
+}
+
+/// Parse the unpacked mods and channels.
+fn process_forge_bufs(bufs: &[u8]) -> Result<ForgeData, MspErr> {
+    let mut reader = ForgeReader { bufs, idx: 0 };
+    let truncated = reader.read_u8()? != 0;
+    let mods_size = u16::from_be_bytes([reader.read_u8()?, reader.read_u8()?]);
+    let mut mods = Vec::new();
+    let mut channels = Vec::new();
+
+    for _ in 0..mods_size {
+        let channel_size_and_flag = reader.read_varint()?;
+        let mod_id = reader.read_str()?;
+        // Mods only required on the server side have no version.
+        let modmarker = match channel_size_and_flag & 0b1 {
+            0 => reader.read_str()?,
+            _ => "".into(),
+        };
+
+        for _ in 0..(channel_size_and_flag >> 1) {
+            channels.push(ForgeChannel {
+                res: format!("{}:{}", mod_id, reader.read_str()?),
+                version: reader.read_str()?,
+                required: reader.read_u8()? != 0,
+            });
+        }
+
+        mods.push(ForgeMod { mod_id, modmarker });
+    }
+
+    for _ in 0..reader.read_varint()? {
+        channels.push(ForgeChannel {
+            res: reader.read_str()?,
+            version: reader.read_str()?,
+            required: reader.read_u8()? != 0,
+        });
+    }
+
+    Ok(ForgeData {
+        mods,
+        channels,
+        truncated,
+        warnings: vec![],
+    })
+}
+
+struct ForgeReader<'a> {
+    bufs: &'a [u8],
+    idx: usize,
+}
+
+impl ForgeReader<'_> {
+    fn read_bufs(&mut self, size: usize) -> Result<&[u8], MspErr> {
+        let bufs = self
+            .bufs
+            .get(self.idx..self.idx + size)
+            .ok_or_else(|| MspErr::DataErr("Incomplete forgeData".into()))?;
+
+        self.idx += size;
+
+        Ok(bufs)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, MspErr> {
+        Ok(self.read_bufs(1)?[0])
+    }
+
+    fn read_varint(&mut self) -> Result<i32, MspErr> {
+        let (len, num) = decode_varint_from_bufs(&self.bufs[self.idx.min(self.bufs.len())..])?;
+
+        self.idx += len;
+
+        Ok(num)
+    }
+
+    fn read_str(&mut self) -> Result<String, MspErr> {
+        let len = self.read_varint()?;
+
+        if len < 0 {
+            return Err(MspErr::DataErr(format!("Invalid string length: {}", len)));
+        }
+
+        Ok(String::from_utf8_lossy(self.read_bufs(len as usize)?).into())
+    }
+}
+
+#[cfg(test)]
+mod forge_test {
+    use super::*;
+    use crate::varint::encode_varint;
+
+    /// Counterpart of [decode_optimized], see `ServerStatusPing#encodeOptimized` of Forge.
+    fn encode_optimized(bufs: &[u8]) -> String {
+        let mut result = vec![
+            bufs.len() as u32 & 0x7FFF,
+            (bufs.len() as u32 >> 15) & 0x7FFF,
+        ];
+        let mut buffer = 0u32;
+        let mut bits_in_buf = 0;
+
+        for &buf in bufs {
+            if bits_in_buf >= 15 {
+                result.push(buffer & 0x7FFF);
+                buffer >>= 15;
+                bits_in_buf -= 15;
+            }
+
+            buffer |= (buf as u32) << bits_in_buf;
+            bits_in_buf += 8;
+        }
+
+        if bits_in_buf > 0 {
+            result.push(buffer & 0x7FFF);
+        }
+
+        result.into_iter().filter_map(char::from_u32).collect()
+    }
+
+    fn str_bufs(str: &str) -> Vec<u8> {
+        let mut bufs = encode_varint(str.len() as i32);
+
+        bufs.extend_from_slice(str.as_bytes());
+        bufs
+    }
+
+    #[test]
+    fn test_packed_forge_data() {
+        // Not truncated, 2 mods.
+        let mut bufs = vec![0x00, 0x00, 0x02];
+
+        // "forge" with version and one channel.
+        bufs.extend(encode_varint(1 << 1));
+        bufs.extend(str_bufs("forge"));
+        bufs.extend(str_bufs("47.1.0"));
+        bufs.extend(str_bufs("tier_sorting"));
+        bufs.extend(str_bufs("1.0"));
+        bufs.push(0x00);
+        // Server only mod.
+        bufs.extend(encode_varint(0b1));
+        bufs.extend(str_bufs("serverutils"));
+        // One non-mod channel.
+        bufs.extend(encode_varint(1));
+        bufs.extend(str_bufs("minecraft:register"));
+        bufs.extend(str_bufs("FML3"));
+        bufs.push(0x01);
+
+        assert_eq!(decode_optimized(&encode_optimized(&bufs)).unwrap(), bufs);
+        // Maximum length declared by two characters, without any data.
+        assert!(decode_optimized("\u{7FFF}\u{7FFF}").is_err());
+        assert!(decode_optimized("\u{0005}\u{0000}ab").is_err());
+
+        let forge_data: ForgeData = serde_json::from_value::<ForgeDataRepr>(serde_json::json!({
+            "channels": [],
+            "mods": [],
+            "fmlNetworkVersion": 3,
+            "truncated": false,
+            "d": encode_optimized(&bufs),
+        }))
+        .unwrap()
+        .into();
+
+        assert_eq!(forge_data.mods.len(), 2);
+        assert_eq!(forge_data.mods[0].mod_id, "forge");
+        assert_eq!(forge_data.mods[0].modmarker, "47.1.0");
+        assert_eq!(forge_data.mods[1].modmarker, "");
+        assert_eq!(forge_data.channels.len(), 2);
+        assert_eq!(forge_data.channels[0].res, "forge:tier_sorting");
+        assert!(forge_data.channels[1].required);
+    }
+
+    #[test]
+    fn test_malformed_forge_data() {
+        let forge_data: ForgeData = serde_json::from_value(serde_json::json!({
+            "mods": [
+                { "modId": "forge", "modmarker": "36.2.39" },
+                { "modId": 1 },
+            ],
+            "channels": [
+                { "res": "fml:handshake", "version": "1.2.3.4", "required": "true", "extra": 0 },
+                { "res": "fml:play", "version": "FML2", "required": 0 },
+            ],
+            "truncated": false,
+        }))
+        .unwrap();
+
+        assert_eq!(forge_data.mods.len(), 1);
+        assert_eq!(forge_data.channels.len(), 2);
+        assert!(forge_data.channels[0].required);
+        assert!(!forge_data.channels[1].required);
+        assert_eq!(forge_data.warnings.len(), 1);
+        assert!(forge_data.warnings[0].starts_with("Cannot parse forgeData.mods[1]"));
+
+        let forge_data: ForgeData =
+            serde_json::from_value(serde_json::json!({ "mods": {}, "channels": null })).unwrap();
+
+        assert!(forge_data.mods.is_empty());
+        assert_eq!(forge_data.warnings.len(), 1);
+    }
+}
@@ -1,16 +1,18 @@
 use crate::{
     conf::Conf,
-    share::{bufs_to_utf16_str, create_tcp_socket},
-    MspErr,
+    protocol::{legacy_protocol_to_version_names, protocol_to_version_names},
+    share::{bufs_to_utf16_str, create_tcp_socket, display_json, ToJson},
+    Description, MspErr, PacketStream,
 };
 use serde::Serialize;
-use std::io::{Read, Write};
 
 /// Legacy server info type.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct LegacyServer {
-    /// Protocol version.
+    /// Protocol version, `-1` if the server sent a non-numeric one.
     pub protocol_version: i32,
+    /// Protocol version exactly as sent by the server.
+    pub protocol_version_raw: String,
     /// Server version.
     pub server_version: String,
     /// MOTD of the target server.
@@ -19,22 +21,42 @@ pub struct LegacyServer {
     pub online_players: i32,
     /// Max players.
     pub max_players: i32,
+    /// The server answered in the Beta 1.8 to 1.3 format (`motd§online§max`), as some odd
+    /// servers do even to the 1.4 ping. [LegacyServer::protocol_version] is then `-1`,
+    /// and [LegacyServer::protocol_version_raw] and [LegacyServer::server_version] are empty.
+    pub beta_format: bool,
 }
 
 impl std::fmt::Display for LegacyServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
+    }
+}
+
+impl ToJson for LegacyServer {}
+
+impl LegacyServer {
+    /// Release versions using [LegacyServer::protocol_version], see [protocol_to_version_names].
+    ///
+    /// Modern servers also answer the legacy ping, with a protocol number since 1.7. As 47 is
+    /// used by both 1.4.2 and 1.8, the versions of both numbering schemes are returned.
+    pub fn minecraft_versions(&self) -> Vec<&'static str> {
+        let mut versions = legacy_protocol_to_version_names(self.protocol_version);
+
+        versions.extend(protocol_to_version_names(self.protocol_version));
+        versions
+    }
+
+    /// MOTD parsed into a structured [Description], see [Description::from_legacy].
+    pub fn description(&self) -> Description {
+        Description::from_legacy(&self.motd)
     }
 }
 
 /// Legacy beta server info type.
 ///
 /// A server older than Legacy, used by not many people anymore.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct LegacyBetaServer {
     /// MOTD of the target server.
     pub motd: String,
@@ -46,34 +68,51 @@ pub struct LegacyBetaServer {
 
 impl std::fmt::Display for LegacyBetaServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
+    }
+}
+
+impl ToJson for LegacyBetaServer {}
+
+impl LegacyBetaServer {
+    /// MOTD parsed into a structured [Description], see [Description::from_legacy].
+    pub fn description(&self) -> Description {
+        Description::from_legacy(&self.motd)
     }
 }
 
 /// Server [before 1.5](https://wiki.vg/Server_List_Ping#1.4_to_1.5)
 pub fn get_legacy_server_status(conf: &Conf) -> Result<LegacyServer, MspErr> {
-    let mut socket = create_tcp_socket(conf)?;
+    get_legacy_server_status_over(&mut create_tcp_socket(conf)?)
+}
+
+/// Same as [get_legacy_server_status], over an already connected stream, see [PacketStream].
+pub fn get_legacy_server_status_over<S: PacketStream>(
+    stream: &mut S,
+) -> Result<LegacyServer, MspErr> {
     let mut bufs = Vec::<u8>::new();
 
-    socket.write(&mut Vec::from([0xFE, 0x01]))?;
-    socket.read_to_end(&mut bufs)?;
+    stream.write_all(&[0xFE, 0x01])?;
+    stream.read_to_end(&mut bufs)?;
 
     process_legacy_server_bufs(bufs.as_slice())
 }
 
 pub fn get_beta_legacy_server_status(conf: &Conf) -> Result<LegacyBetaServer, MspErr> {
-    let mut socket = create_tcp_socket(conf)?;
+    get_beta_legacy_server_status_over(&mut create_tcp_socket(conf)?)
+}
+
+/// Same as [get_beta_legacy_server_status], over an already connected stream, see [PacketStream].
+pub fn get_beta_legacy_server_status_over<S: PacketStream>(
+    stream: &mut S,
+) -> Result<LegacyBetaServer, MspErr> {
     let mut bufs = [0u8; 1];
 
     // Prior to Minecraft 1.4, the client only sends 0xFE.
-    socket.write(&[0xFE])?;
-    socket.read(&mut bufs)?;
+    stream.write_all(&[0xFE])?;
+    stream.read_exact(&mut bufs)?;
 
-    if bufs.get(0) != Some(&0xFF) {
+    if bufs[0] != 0xFF {
         return Err(MspErr::DataErr(format!(
             "Packet response excepted start with: 0xFF, but got: 0x{:02X}",
             bufs[0]
@@ -84,16 +123,72 @@ pub fn get_beta_legacy_server_status(conf: &Conf) -> Result<LegacyBetaServer, Ms
     //
     // For unknown reasons (likely due to encoding), this needs to be divided by 2.
     let mut bufs = [0u8; 2];
-    socket.read(&mut bufs)?;
+    stream.read_exact(&mut bufs)?;
 
-    let mut bufs = vec![0u8; (u16::from_be_bytes(bufs) * 2) as usize];
+    let mut bufs = vec![0u8; u16::from_be_bytes(bufs) as usize * 2];
 
-    socket.read(&mut bufs)?;
+    stream.read_exact(&mut bufs)?;
 
-    let server_info = bufs_to_utf16_str(bufs.as_slice())?;
-    let server_split = server_info.split("§").collect::<Vec<_>>();
+    process_beta_legacy_server_bufs(bufs.as_slice())
+}
 
-    build_beta_legacy_server(server_split)
+/// Async version of [get_legacy_server_status].
+#[cfg(feature = "tokio")]
+pub async fn get_legacy_server_status_async(conf: &Conf) -> Result<LegacyServer, MspErr> {
+    use crate::share::{create_tcp_socket_async, overall_timeout_async, timeout_async};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    overall_timeout_async(conf, async {
+        let mut socket = create_tcp_socket_async(conf).await?;
+        let mut bufs = Vec::<u8>::new();
+
+        timeout_async(
+            conf.socket_conf.write_timeout,
+            socket.write_all(&[0xFE, 0x01]),
+        )
+        .await?;
+        timeout_async(
+            conf.socket_conf.read_time_out,
+            socket.read_to_end(&mut bufs),
+        )
+        .await?;
+
+        process_legacy_server_bufs(bufs.as_slice())
+    })
+    .await
+}
+
+/// Async version of [get_beta_legacy_server_status].
+#[cfg(feature = "tokio")]
+pub async fn get_beta_legacy_server_status_async(conf: &Conf) -> Result<LegacyBetaServer, MspErr> {
+    use crate::share::{create_tcp_socket_async, overall_timeout_async, timeout_async};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    overall_timeout_async(conf, async {
+        let read_time_out = conf.socket_conf.read_time_out;
+        let mut socket = create_tcp_socket_async(conf).await?;
+
+        // Prior to Minecraft 1.4, the client only sends 0xFE.
+        timeout_async(conf.socket_conf.write_timeout, socket.write_all(&[0xFE])).await?;
+
+        let first_buf = timeout_async(read_time_out, socket.read_u8()).await?;
+
+        if first_buf != 0xFF {
+            return Err(MspErr::DataErr(format!(
+                "Packet response excepted start with: 0xFF, but got: 0x{:02X}",
+                first_buf
+            )));
+        }
+
+        // Read packet data length, see [get_beta_legacy_server_status].
+        let len = timeout_async(read_time_out, socket.read_u16()).await?;
+        let mut bufs = vec![0u8; len as usize * 2];
+
+        timeout_async(read_time_out, socket.read_exact(&mut bufs)).await?;
+
+        process_beta_legacy_server_bufs(bufs.as_slice())
+    })
+    .await
 }
 
 fn build_legacy_server(data: Vec<&str>) -> Result<LegacyServer, MspErr> {
@@ -112,11 +207,14 @@ fn build_legacy_server(data: Vec<&str>) -> Result<LegacyServer, MspErr> {
     }
 
     Ok(LegacyServer {
-        protocol_version: data[0].parse::<i32>()?,
+        // Some modified servers send a non-numeric protocol version.
+        protocol_version: data[0].parse::<i32>().unwrap_or(-1),
+        protocol_version_raw: data[0].into(),
         server_version: data[1].into(),
         motd: data[2].into(),
         online_players: data[3].parse::<i32>()?,
         max_players: data[4].parse::<i32>()?,
+        beta_format: false,
     })
 }
 
@@ -134,29 +232,101 @@ fn build_beta_legacy_server(data: Vec<&str>) -> Result<LegacyBetaServer, MspErr>
     }
 
     Ok(LegacyBetaServer {
-        motd: data[2].into(),
-        online_players: data[3].parse::<i32>()?,
-        max_players: data[4].parse::<i32>()?,
+        motd: data[0].into(),
+        online_players: data[1].parse::<i32>()?,
+        max_players: data[2].parse::<i32>()?,
     })
 }
 
+/// Parse the UTF-16 encoded beta legacy server info into [LegacyBetaServer].
+fn process_beta_legacy_server_bufs(bufs: &[u8]) -> Result<LegacyBetaServer, MspErr> {
+    let server_info = bufs_to_utf16_str(bufs)?;
+    let server_split = server_info.split("§").collect::<Vec<_>>();
+
+    build_beta_legacy_server(server_split)
+}
+
 pub fn process_legacy_server_bufs(bufs: &[u8]) -> Result<LegacyServer, MspErr> {
-    if bufs.get(0) != Some(&0xFF) {
-        return Err(MspErr::DataErr(format!(
-            "Packet response excepted start with: 0xFF, but got: 0x{:02X}",
-            bufs[0]
-        )));
+    match bufs.first() {
+        Some(&0xFF) => {}
+        first_buf => {
+            return Err(MspErr::DataErr(format!(
+                "Packet response excepted start with: 0xFF, but got: {:02X?}",
+                first_buf
+            )));
+        }
     }
 
-    let server_info = bufs_to_utf16_str(&bufs[3..])?;
+    let server_info = bufs_to_utf16_str(bufs.get(3..).unwrap_or_default())?;
 
     if !server_info.starts_with("§1") {
-        return Err(MspErr::DataErr(
-            "Server info excepted start with: §1.".into(),
-        ));
+        log::debug!("Server info does not start with §1, parse it as the beta format.");
+
+        let server = build_beta_legacy_server(server_info.split("§").collect::<Vec<_>>())?;
+
+        return Ok(LegacyServer {
+            protocol_version: -1,
+            protocol_version_raw: "".into(),
+            server_version: "".into(),
+            motd: server.motd,
+            online_players: server.online_players,
+            max_players: server.max_players,
+            beta_format: true,
+        });
     }
 
     Ok(build_legacy_server(
         server_info.split("\0").skip(1).collect::<Vec<_>>(),
     )?)
 }
+
+#[cfg(test)]
+mod legacy_server_test {
+    use super::*;
+
+    #[test]
+    fn test_build_beta_legacy_server() {
+        let server = build_beta_legacy_server(vec!["A Minecraft Server", "3", "20"]);
+        assert!(server.is_ok());
+
+        let server = server.unwrap();
+        assert_eq!(server.motd, "A Minecraft Server");
+        assert_eq!(server.online_players, 3);
+        assert_eq!(server.max_players, 20);
+
+        assert!(build_beta_legacy_server(vec!["A Minecraft Server", "3"]).is_err());
+        assert!(build_beta_legacy_server(vec!["A Minecraft Server", "three", "20"]).is_err());
+    }
+
+    #[test]
+    fn test_build_legacy_server() {
+        let server = build_legacy_server(vec!["47", "1.4.2", "A Minecraft Server", "3", "20"]);
+        let server = server.unwrap();
+
+        assert_eq!(server.protocol_version, 47);
+        assert_eq!(server.minecraft_versions()[..2], ["1.4.2", "1.8"]);
+
+        let server =
+            build_legacy_server(vec!["Paper-47", "1.4.2", "A Minecraft Server", "3", "20"])
+                .unwrap();
+        assert_eq!(server.protocol_version, -1);
+        assert_eq!(server.protocol_version_raw, "Paper-47");
+    }
+
+    #[test]
+    fn test_process_legacy_server_bufs_beta_format() {
+        let server_info = "A Minecraft Server§3§20".encode_utf16().collect::<Vec<_>>();
+        let mut bufs = vec![0xFF];
+
+        bufs.extend_from_slice(&(server_info.len() as u16).to_be_bytes());
+        bufs.extend(server_info.iter().flat_map(|ch| ch.to_be_bytes()));
+
+        let server = process_legacy_server_bufs(&bufs).unwrap();
+
+        assert!(server.beta_format);
+        assert_eq!(server.protocol_version, -1);
+        assert_eq!(server.motd, "A Minecraft Server");
+        assert_eq!(server.max_players, 20);
+        assert!(process_legacy_server_bufs(&[]).is_err());
+    }
+}
@@ -1,9 +1,14 @@
+mod auto_detect;
 mod bedrock_server;
+mod forge;
 mod legacy_server;
 mod netty_server;
 mod regular_server;
+mod status;
 
+pub use auto_detect::*;
 pub use bedrock_server::*;
 pub use legacy_server::*;
 pub use netty_server::*;
 pub use regular_server::*;
+pub use status::*;
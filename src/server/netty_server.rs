@@ -1,37 +1,110 @@
 use super::{process_legacy_server_bufs, LegacyServer};
-use crate::{conf::Conf, share::create_tcp_socket, MspErr};
-use std::io::{Read, Write};
+use crate::{conf::Conf, share::create_tcp_socket, MspErr, PacketStream};
+
+/// Protocol version sent in the `MC|PingHost` packet, 74 is the last 1.6 version (1.6.2).
+const NETTY_PROTOCOL_VERSION: u8 = 0x4A;
 
 /// The alias of [LegacyServer] is identical in content.
 pub type NettyServer = LegacyServer;
 
 pub fn get_netty_server_status(conf: &Conf) -> Result<NettyServer, MspErr> {
-    let mut socket = create_tcp_socket(conf)?;
+    get_netty_server_status_over(&mut create_tcp_socket(conf)?, conf)
+}
+
+/// Same as [get_netty_server_status], over an already connected stream, see [PacketStream].
+pub fn get_netty_server_status_over<S: PacketStream>(
+    stream: &mut S,
+    conf: &Conf,
+) -> Result<NettyServer, MspErr> {
+    let mut bufs = Vec::new();
+
+    stream.write_all(&build_netty_ping_packet(conf))?;
+    stream.read_to_end(&mut bufs)?;
+
+    process_legacy_server_bufs(bufs.as_slice())
+}
+
+/// Async version of [get_netty_server_status].
+#[cfg(feature = "tokio")]
+pub async fn get_netty_server_status_async(conf: &Conf) -> Result<NettyServer, MspErr> {
+    use crate::share::{create_tcp_socket_async, overall_timeout_async, timeout_async};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    overall_timeout_async(conf, async {
+        let mut socket = create_tcp_socket_async(conf).await?;
+        let mut bufs = Vec::new();
+
+        timeout_async(
+            conf.socket_conf.write_timeout,
+            socket.write_all(&build_netty_ping_packet(conf)),
+        )
+        .await?;
+        timeout_async(
+            conf.socket_conf.read_time_out,
+            socket.read_to_end(&mut bufs),
+        )
+        .await?;
+
+        process_legacy_server_bufs(bufs.as_slice())
+    })
+    .await
+}
+
+/// Build the [1.6 server list ping](https://wiki.vg/Server_List_Ping#1.6) packet,
+/// followed by the `MC|PingHost` plugin message with protocol version, host and port.
+fn build_netty_ping_packet(conf: &Conf) -> Vec<u8> {
+    let host = conf.handshake_host.as_ref().unwrap_or(&conf.host);
+    let host_u16 = host.encode_utf16().collect::<Vec<_>>();
     let mut packet_data = Vec::<u8>::new();
-    let host_u16 = conf.host.encode_utf16().collect::<Vec<_>>();
 
     packet_data.append(&mut vec![
         0xFE, 0x01, 0xFA, 0x00, 0x0B, 0x00, 0x4D, 0x00, 0x43, 0x00, 0x7C, 0x00, 0x50, 0x00, 0x69,
         0x00, 0x6E, 0x00, 0x67, 0x00, 0x48, 0x00, 0x6F, 0x00, 0x73, 0x00, 0x74,
     ]);
-    packet_data.append(&mut ((7 + host_u16.len()) as u16).to_be_bytes().to_vec());
-    // Protocol version
-    packet_data.push(0x50);
-    packet_data.append(&mut (conf.host.len() as u16).to_be_bytes().to_vec());
-    packet_data.append(
-        &mut host_u16
-            .iter()
-            .map(|x| x.to_be_bytes().to_vec())
-            .flatten()
-            .collect(),
-    );
+    // Length of the rest data: protocol version(1), host length(2), host, port(4)
+    packet_data.extend_from_slice(&((7 + host_u16.len() * 2) as u16).to_be_bytes());
+    packet_data.push(NETTY_PROTOCOL_VERSION);
+    // Host length in UTF-16 characters
+    packet_data.extend_from_slice(&(host_u16.len() as u16).to_be_bytes());
+    packet_data.extend(host_u16.iter().flat_map(|x| x.to_be_bytes()));
     // Server port
-    packet_data.append(&mut (conf.port as u32).to_be_bytes().to_vec());
-    socket.write(&mut vec![0xFE, 0x01])?;
+    packet_data.extend_from_slice(&(conf.port as u32).to_be_bytes());
 
-    let mut bufs = Vec::new();
+    packet_data
+}
 
-    socket.read_to_end(&mut bufs)?;
+#[cfg(test)]
+mod netty_server_test {
+    use super::*;
 
-    process_legacy_server_bufs(bufs.as_slice())
+    #[test]
+    fn test_build_netty_ping_packet() {
+        // Example from https://wiki.vg/Server_List_Ping#1.6
+        assert_eq!(
+            build_netty_ping_packet(&Conf::create_with_port("localhost", 25565)),
+            vec![
+                0xFE, 0x01, 0xFA, 0x00, 0x0B, 0x00, 0x4D, 0x00, 0x43, 0x00, 0x7C, 0x00, 0x50, 0x00,
+                0x69, 0x00, 0x6E, 0x00, 0x67, 0x00, 0x48, 0x00, 0x6F, 0x00, 0x73, 0x00, 0x74, 0x00,
+                0x19, 0x4A, 0x00, 0x09, 0x00, 0x6C, 0x00, 0x6F, 0x00, 0x63, 0x00, 0x61, 0x00, 0x6C,
+                0x00, 0x68, 0x00, 0x6F, 0x00, 0x73, 0x00, 0x74, 0x00, 0x00, 0x63, 0xDD
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_netty_ping_packet_unicode_host() {
+        // 15 bytes in UTF-8, but 5 UTF-16 code units.
+        let packet = build_netty_ping_packet(&Conf::create_with_port("例子.测试", 25565));
+
+        assert_eq!(&packet[27..29], &(7u16 + 5 * 2).to_be_bytes());
+        assert_eq!(&packet[30..32], &5u16.to_be_bytes());
+        assert_eq!(&packet[32..34], &[0x4F, 0x8B]);
+        assert_eq!(packet.len(), 29 + 7 + 5 * 2);
+
+        // Characters outside the BMP take a surrogate pair.
+        let packet = build_netty_ping_packet(&Conf::create_with_port("🎮.mc", 25565));
+
+        assert_eq!(&packet[30..32], &5u16.to_be_bytes());
+        assert_eq!(&packet[32..36], &[0xD8, 0x3C, 0xDF, 0xAE]);
+    }
 }
@@ -1,26 +1,43 @@
+use super::forge::{deserialize_flag, ForgeDataRepr};
 use crate::{
-    share::{create_tcp_socket, get_server_current_time},
-    varint::{decode_varint_from_socket, encode_varint},
-    Conf, MspErr,
+    motd,
+    protocol::protocol_to_version_names,
+    share::{
+        create_tcp_socket, decode_base64, display_json, get_server_current_time, DeadlineTcpStream,
+        ToJson,
+    },
+    varint::{decode_varint_from_bufs, decode_varint_from_reader, encode_varint},
+    Conf, MspErr, PacketStream,
 };
+use flate2::read::ZlibDecoder;
 use serde::{Deserialize, Serialize};
-use std::{
-    io::{Read, Write},
-    net::TcpStream,
-};
+use std::{io::Read, net::SocketAddr, time::Duration};
 
-const DEFAULT_SERVER_PORT: u16 = 25565;
+const DEFAULT_PROTOCOL_VERSION: i32 = -1;
+const MAX_PACKET_SIZE: i32 = 2097151;
+const STATUS_RESPONSE_ID: i32 = 0x00;
+const PONG_RESPONSE_ID: i32 = 0x01;
+const SET_COMPRESSION_ID: i32 = 0x03;
+const FAVICON_PREFIX: &str = "data:image/png;base64,";
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+/// Values of the required status response fields, in place of the ones that cannot be parsed.
+const LENIENT_BASE: &str = r#"{"version":{"name":"","protocol":-1},"description":""}"#;
 
 /// Regular Server info type.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Two statuses are equal when the server reports the same data, the connection details
+/// [Server::ping] and [Server::addr], and [Server::errors] are not compared.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Server {
     /// Server version. Includes version name and protocol number.
     pub version: Version,
-    /// Server Player info.
+    /// Server Player info, default if the server sends `null` or leaves it out.
+    #[serde(default, deserialize_with = "deserialize_players")]
     pub players: Players,
     /// Server description, similar to MOTD.
     pub description: Description,
-    /// Server icon, base64 encoding.
+    /// Server icon, base64 encoding. Empty if the server has no icon.
+    #[serde(default)]
     pub favicon: String,
 
     /// Forge plugin server unique information, including mod and channel info.
@@ -39,36 +56,196 @@ pub struct Server {
     )]
     pub enforces_secure_chat: bool,
 
+    /// Whether the server previews chat messages, sent by 1.19 to 1.19.2 servers only.
+    #[serde(
+        alias = "previewsChat",
+        rename = "previewsChat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub previews_chat: Option<bool>,
+
+    /// Whether chat reporting is disabled, sent by servers running mods or
+    /// plugins such as No Chat Reports.
+    #[serde(
+        alias = "preventsChatReports",
+        rename = "preventsChatReports",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prevents_chat_reports: Option<bool>,
+
     /// Server latency, in milliseconds.
     #[serde(default = "ping_default")]
     pub ping: u64,
+
+    /// Address that actually answered, useful when the host resolves to several
+    /// addresses, see [SocketConf::ip_preference](crate::SocketConf::ip_preference).
+    /// `None` when connected through [Conf::proxy].
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+
+    /// Fields of the status response that could not be parsed in [Conf::lenient] mode,
+    /// which are left at their default instead.
+    #[serde(skip)]
+    pub errors: Vec<MspErr>,
+}
+
+impl Server {
+    /// Decode [Server::favicon] into raw PNG bytes.
+    ///
+    /// Return an empty [Vec] if the server has no favicon, and [MspErr::DataErr] if the
+    /// `data:image/png;base64,` prefix is missing or the payload is malformed.
+    pub fn favicon_png(&self) -> Result<Vec<u8>, MspErr> {
+        if self.favicon.is_empty() {
+            return Ok(vec![]);
+        }
+
+        match self.favicon.strip_prefix(FAVICON_PREFIX) {
+            Some(payload) => decode_base64(payload),
+            None => Err(MspErr::DataErr(format!(
+                "Favicon excepted start with: {}",
+                FAVICON_PREFIX
+            ))),
+        }
+    }
+
+    /// Width and height of the favicon, read from the IHDR chunk of the PNG.
+    ///
+    /// Vanilla servers require a 64×64 favicon. Return [MspErr::DataErr] if the server has
+    /// no favicon, or it is not a PNG.
+    pub fn favicon_dimensions(&self) -> Result<(u32, u32), MspErr> {
+        let png = self.favicon_png()?;
+
+        if png.is_empty() {
+            return Err(MspErr::DataErr("Server has no favicon".into()));
+        }
+
+        // The IHDR chunk always comes first, right after the signature and its
+        // length (4 bytes), followed by the width and height (4 bytes each).
+        match (png.get(..8), png.get(12..16), png.get(16..24)) {
+            (Some(signature), Some(b"IHDR"), Some(size)) if signature == PNG_SIGNATURE => Ok((
+                u32::from_be_bytes([size[0], size[1], size[2], size[3]]),
+                u32::from_be_bytes([size[4], size[5], size[6], size[7]]),
+            )),
+            _ => Err(MspErr::DataErr("Favicon is not a valid PNG".into())),
+        }
+    }
+
+    /// Compact human-readable summary: version name, online/max players,
+    /// plain-text MOTD (on a single line) and ping.
+    ///
+    /// Unlike the [Display](std::fmt::Display) output, which is the full JSON, this is
+    /// intended for terminal users.
+    pub fn summary(&self) -> String {
+        let motd = self
+            .description
+            .to_plain()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        format!(
+            "{} | {}/{} players | {} | {}ms",
+            self.version.name, self.players.online, self.players.max, motd, self.ping
+        )
+    }
+
+    /// Whether the server reports Forge mods in [Server::forge_data].
+    pub fn is_modded(&self) -> bool {
+        self.mod_count() > 0
+    }
+
+    /// Number of Forge mods in [Server::forge_data], 0 for a vanilla server.
+    pub fn mod_count(&self) -> usize {
+        self.forge_data
+            .as_ref()
+            .map_or(0, |forge_data| forge_data.mods.len())
+    }
+}
+
+impl PartialEq for Server {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.players == other.players
+            && self.description == other.description
+            && self.favicon == other.favicon
+            && self.forge_data == other.forge_data
+            && self.enforces_secure_chat == other.enforces_secure_chat
+            && self.previews_chat == other.previews_chat
+            && self.prevents_chat_reports == other.prevents_chat_reports
+    }
 }
 
 impl std::fmt::Display for Server {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?
-        )
+        display_json(self, f)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+impl ToJson for Server {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Version {
     pub name: String,
     pub protocol: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Version {
+    /// Release versions using [Version::protocol], see [protocol_to_version_names].
+    ///
+    /// Unlike [Version::name], which proxies and modded servers often replace
+    /// with custom text, this tells the actual version.
+    pub fn minecraft_versions(&self) -> Vec<&'static str> {
+        protocol_to_version_names(self.protocol)
+    }
+}
+
+/// Server player info.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(deny_unknown_fields, default)]
 pub struct Players {
+    /// Max players.
     pub max: i32,
+    /// Online players.
     pub online: i32,
+    /// A subset of the online players, servers usually cap it (12 for vanilla).
+    ///
+    /// Decorative entries with the nil UUID, used by some servers to show custom
+    /// hover text, are filtered out. See [Players::is_sample_truncated].
+    #[serde(deserialize_with = "deserialize_sample")]
     pub sample: Vec<Player>,
 }
 
+impl Players {
+    /// Whether [Players::sample] lists fewer players than are online.
+    pub fn is_sample_truncated(&self) -> bool {
+        (self.sample.len() as i64) < self.online as i64
+    }
+}
+
+/// Treat `"players": null` as the default player info.
+fn deserialize_players<'de, D>(deserializer: D) -> Result<Players, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<Players>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Drop the placeholder entries from the player sample, which may also be `null`.
+fn deserialize_sample<'de, D>(deserializer: D) -> Result<Vec<Player>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<Vec<Player>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|player| !player.is_placeholder())
+        .collect())
+}
+
 impl Default for Players {
     fn default() -> Self {
         Players {
@@ -79,18 +256,145 @@ impl Default for Players {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+/// Player listed in the sample of online players.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Player {
+    /// Player name, may also be an arbitrary line of text on servers customizing the hover list.
     pub name: String,
+    /// Player UUID in dashed form, see [Player::uuid].
     pub id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields, default)]
+impl Player {
+    /// Whether the entry has the nil UUID, i.e. is a line of text rather than a player.
+    fn is_placeholder(&self) -> bool {
+        !self.id.is_empty() && self.id.chars().all(|ch| ch == '0' || ch == '-')
+    }
+
+    /// Parse [Player::id] from the dashed form (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`) into [u128].
+    ///
+    /// Servers hiding their player list usually send placeholder entries with the nil UUID,
+    /// [MspErr::DataErr] is returned for them as well as for malformed IDs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use msp::Player;
+    /// #
+    /// let player = Player {
+    ///     name: "Notch".into(),
+    ///     id: "069a79f4-44e9-4726-a5be-fca90e38aaf5".into(),
+    /// };
+    ///
+    /// assert_eq!(player.uuid().unwrap(), 0x069a79f444e94726a5befca90e38aaf5);
+    /// #
+    /// # let player = Player {
+    /// #     name: "§aHidden".into(),
+    /// #     id: "00000000-0000-0000-0000-000000000000".into(),
+    /// # };
+    /// # assert!(player.uuid().is_err());
+    /// ```
+    pub fn uuid(&self) -> Result<u128, MspErr> {
+        let is_dashed = self.id.len() == 36
+            && self.id.char_indices().all(|(idx, ch)| match idx {
+                8 | 13 | 18 | 23 => ch == '-',
+                _ => ch.is_ascii_hexdigit(),
+            });
+
+        let uuid = match is_dashed {
+            true => u128::from_str_radix(&self.id.replace('-', ""), 16).ok(),
+            false => None,
+        };
+
+        match uuid {
+            Some(0) => Err(MspErr::DataErr(format!(
+                "Player {} has a placeholder UUID: {}",
+                self.name, self.id
+            ))),
+            Some(uuid) => Ok(uuid),
+            None => Err(MspErr::DataErr(format!("Invalid player UUID: {}", self.id))),
+        }
+    }
+}
+
+/// Server description in [chat component](https://wiki.vg/Chat) format.
+///
+/// Servers may also send the description as a plain string, which is parsed into [Description::text].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "DescriptionRepr")]
 pub struct Description {
+    /// Child components, inheriting the style of their parent.
     pub extra: Vec<DescriptionExtra>,
+    /// Root text, may contain `§` formatting codes.
     pub text: String,
+    /// Root color, see [DescriptionExtra::color].
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub color: String,
+    /// Bold root text.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+    /// Italic root text.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    /// Underlined root text.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub underlined: bool,
+    /// Strikethrough root text.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub strikethrough: bool,
+    /// Obfuscated root text.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub obfuscated: bool,
+    /// Root font, see [DescriptionExtra::font].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Root insertion, see [DescriptionExtra::insertion].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insertion: Option<String>,
+    /// Root click event, see [DescriptionExtra::click_event].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub click_event: Option<serde_json::Value>,
+    /// Root hover event, see [DescriptionExtra::hover_event].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover_event: Option<serde_json::Value>,
+    /// Root keys not modeled above, see [DescriptionExtra::other].
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Shapes of the description in the status response, the root component
+/// has the same keys as its children.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DescriptionRepr {
+    Text(String),
+    Component(DescriptionExtra),
+}
+
+impl From<DescriptionRepr> for Description {
+    fn from(repr: DescriptionRepr) -> Self {
+        match repr {
+            DescriptionRepr::Text(text) => Description {
+                text,
+                ..Default::default()
+            },
+            DescriptionRepr::Component(component) => Description {
+                extra: component.extra,
+                text: component.text,
+                color: component.color,
+                bold: component.bold,
+                italic: component.italic,
+                underlined: component.underlined,
+                strikethrough: component.strikethrough,
+                obfuscated: component.obfuscated,
+                font: component.font,
+                insertion: component.insertion,
+                click_event: component.click_event,
+                hover_event: component.hover_event,
+                other: component.other,
+            },
+        }
+    }
 }
 
 impl Default for Description {
@@ -98,21 +402,101 @@ impl Default for Description {
         Description {
             extra: vec![],
             text: "".into(),
+            color: "".into(),
+            bold: false,
+            italic: false,
+            underlined: false,
+            strikethrough: false,
+            obfuscated: false,
+            font: None,
+            insertion: None,
+            click_event: None,
+            hover_event: None,
+            other: serde_json::Map::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields, default)]
+impl Description {
+    /// Build a description from a `§`-coded MOTD, as returned by the legacy,
+    /// Query and Bedrock protocols. Each styled segment becomes a [DescriptionExtra].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use msp::Description;
+    /// #
+    /// let description = Description::from_legacy("§cA §lMinecraft Server");
+    ///
+    /// assert_eq!(description.extra.len(), 2);
+    /// assert_eq!(description.extra[1].color, "red");
+    /// assert!(description.extra[1].bold);
+    /// assert_eq!(description.to_plain(), "A Minecraft Server");
+    /// ```
+    pub fn from_legacy(motd: &str) -> Self {
+        Description {
+            extra: motd::parse_formatting_codes(motd),
+            ..Default::default()
+        }
+    }
+
+    /// Readable text of the description, with all components joined
+    /// and `§` formatting codes stripped.
+    pub fn to_plain(&self) -> String {
+        motd::description_to_plain(self)
+    }
+
+    /// Text of the description with colors and formatting converted
+    /// to ANSI escape sequences, for terminal display.
+    pub fn to_ansi(&self) -> String {
+        motd::description_to_ansi(self)
+    }
+}
+
+/// A styled [chat component](https://wiki.vg/Chat) of [Description].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct DescriptionExtra {
+    /// Named color (e.g. `red`) or `#rrggbb` hex color, empty to inherit the parent.
     pub color: String,
+    /// Bold text.
     pub bold: bool,
+    /// Italic text.
     pub italic: bool,
+    /// Underlined text.
     pub underlined: bool,
+    /// Strikethrough text.
     pub strikethrough: bool,
+    /// Obfuscated (randomly changing) text.
     pub obfuscated: bool,
+    /// Component text.
     pub text: String,
+    /// Child components.
     pub extra: Vec<DescriptionExtra>,
+    /// Resource location of the font, e.g. `minecraft:uniform`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Text inserted into the chat input when shift-clicked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insertion: Option<String>,
+    /// Action on click, kept as is since its shape changed in 1.21.5.
+    #[serde(alias = "clickEvent", skip_serializing_if = "Option::is_none")]
+    pub click_event: Option<serde_json::Value>,
+    /// Tooltip on hover, kept as is since its shape changed in 1.21.5.
+    #[serde(alias = "hoverEvent", skip_serializing_if = "Option::is_none")]
+    pub hover_event: Option<serde_json::Value>,
+    /// Keys not modeled above, e.g. `shadow_color`, `translate` or `score`.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl DescriptionExtra {
+    /// RGB of [DescriptionExtra::color], using the vanilla client colors for named colors.
+    ///
+    /// Returns `None` if the color is empty, i.e. inherited, or unknown.
+    pub fn color_rgb(&self) -> Option<(u8, u8, u8)> {
+        motd::color_to_rgb(&self.color)
+    }
 }
 
 impl Default for DescriptionExtra {
@@ -126,74 +510,436 @@ impl Default for DescriptionExtra {
             obfuscated: false,
             text: "".into(),
             extra: Vec::new(),
+            font: None,
+            insertion: None,
+            click_event: None,
+            hover_event: None,
+            other: serde_json::Map::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Forge mods and channels, read from the plain arrays of older Forge,
+/// or from the packed `d` field of Forge 1.18.2+.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ForgeDataRepr")]
 pub struct ForgeData {
     pub mods: Vec<ForgeMod>,
     pub channels: Vec<ForgeChannel>,
+    /// Whether the server left out some mods to keep the response small.
+    pub truncated: bool,
+    /// Why some mods or channels could not be read, those are left out
+    /// instead of failing the whole status.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ForgeMod {
     #[serde(alias = "modId", rename = "modId")]
     pub mod_id: String,
     pub modmarker: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ForgeChannel {
     pub res: String,
     pub version: String,
+    /// Sent as a boolean, a number or a string depending on the FML version.
+    #[serde(default, deserialize_with = "deserialize_flag")]
     pub required: bool,
 }
 
 pub fn get_server_status(conf: &Conf) -> Result<Server, MspErr> {
     let mut socket = create_tcp_socket(conf)?;
-    let hand_shake_packet = build_handshake_packet(&conf);
+    let mut server = get_server_status_over(&mut socket, conf)?;
+
+    if conf.proxy.is_none() {
+        server.addr = socket.peer_addr().ok();
+    }
+
+    close_tcp_socket(&socket, conf);
+
+    Ok(server)
+}
+
+/// Shut down the connection if [SocketConf::graceful_close](crate::SocketConf::graceful_close) is set.
+///
+/// The exchange is already complete, so a failure is only logged.
+fn close_tcp_socket(socket: &DeadlineTcpStream, conf: &Conf) {
+    if conf.socket_conf.graceful_close {
+        if let Err(err) = socket.shutdown(std::net::Shutdown::Both) {
+            log::debug!("Cannot shut down the connection: {}", err);
+        }
+    }
+}
+
+/// Async version of [close_tcp_socket], only the write half can be shut down.
+#[cfg(feature = "tokio")]
+async fn close_tcp_socket_async(socket: &mut tokio::net::TcpStream, conf: &Conf) {
+    use crate::share::timeout_async;
+    use tokio::io::AsyncWriteExt;
+
+    if conf.socket_conf.graceful_close {
+        if let Err(err) = timeout_async(conf.socket_conf.write_timeout, socket.shutdown()).await {
+            log::debug!("Cannot shut down the connection: {}", err);
+        }
+    }
+}
+
+/// Same as [get_server_status], over an already connected stream, see [PacketStream].
+pub fn get_server_status_over<S: PacketStream>(
+    stream: &mut S,
+    conf: &Conf,
+) -> Result<Server, MspErr> {
+    let (data_buffer, compression) = request_server_status_over(stream, conf)?;
+    let mut server = process_server_bufs_for(&data_buffer, conf)?;
+
+    // Get server ping
+    if conf.socket_conf.measure_ping {
+        server.ping = get_server_ping(stream, compression)?;
+    }
+
+    Ok(server)
+}
+
+/// Get the server status over a TCP stream connected by the caller, e.g. through
+/// an SSH port-forward or a custom tunnel.
+///
+/// `host` and `port` are only sent in the handshake packet, the stream is used as is,
+/// so its timeouts are left to the caller. See [Conf::get_server_status_over] for
+/// other kinds of streams and options.
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{get_server_status_on, MspErr};
+/// use std::net::TcpStream;
+///
+/// fn main() -> Result<(), MspErr> {
+///     // Local end of `ssh -L 25566:mc.example.com:25565 ...`
+///     let stream = TcpStream::connect("127.0.0.1:25566")?;
+///     let info = get_server_status_on(stream, "mc.example.com", 25565)?;
+///
+///     Ok(())
+/// }
+/// ```
+pub fn get_server_status_on(
+    mut stream: std::net::TcpStream,
+    host: &str,
+    port: u16,
+) -> Result<Server, MspErr> {
+    let conf = Conf::create_with_port(host, port);
+    let mut server = get_server_status_over(&mut stream, &conf)?;
+
+    server.addr = stream.peer_addr().ok();
+
+    Ok(server)
+}
+
+/// Async version of [get_server_status].
+#[cfg(feature = "tokio")]
+pub async fn get_server_status_async(conf: &Conf) -> Result<Server, MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        let (mut socket, data_buffer, compression) = request_server_status_async(conf).await?;
+        let mut server = process_server_bufs_for(&data_buffer, conf)?;
+
+        // Get server ping
+        if conf.socket_conf.measure_ping {
+            server.ping = get_server_ping_async(&mut socket, compression, conf).await?;
+        }
+
+        if conf.proxy.is_none() {
+            server.addr = socket.peer_addr().ok();
+        }
+
+        close_tcp_socket_async(&mut socket, conf).await;
+
+        Ok(server)
+    })
+    .await
+}
+
+/// Get the untyped JSON of the status response.
+pub fn get_server_status_raw(conf: &Conf) -> Result<serde_json::Value, MspErr> {
+    let (_, data_buffer, _) = request_server_status(conf)?;
+
+    process_server_raw_bufs(&data_buffer)
+}
+
+/// Async version of [get_server_status_raw].
+#[cfg(feature = "tokio")]
+pub async fn get_server_status_raw_async(conf: &Conf) -> Result<serde_json::Value, MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        let (_, data_buffer, _) = request_server_status_async(conf).await?;
+
+        process_server_raw_bufs(&data_buffer)
+    })
+    .await
+}
+
+/// Get the JSON bytes of the status response exactly as received.
+pub fn get_server_status_bytes(conf: &Conf) -> Result<Vec<u8>, MspErr> {
+    let (_, data_buffer, _) = request_server_status(conf)?;
+
+    status_response_str(&data_buffer)?;
+
+    Ok(data_buffer)
+}
+
+/// Async version of [get_server_status_bytes].
+#[cfg(feature = "tokio")]
+pub async fn get_server_status_bytes_async(conf: &Conf) -> Result<Vec<u8>, MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        let (_, data_buffer, _) = request_server_status_async(conf).await?;
+
+        status_response_str(&data_buffer)?;
+
+        Ok(data_buffer)
+    })
+    .await
+}
+
+/// Get only the online and max player counts of the status response.
+pub fn get_player_counts(conf: &Conf) -> Result<(i32, i32), MspErr> {
+    let (_, data_buffer, _) = request_server_status(conf)?;
+
+    process_player_counts_bufs(&data_buffer)
+}
+
+/// Async version of [get_player_counts].
+#[cfg(feature = "tokio")]
+pub async fn get_player_counts_async(conf: &Conf) -> Result<(i32, i32), MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        let (_, data_buffer, _) = request_server_status_async(conf).await?;
+
+        process_player_counts_bufs(&data_buffer)
+    })
+    .await
+}
+
+/// Measure the round-trip latency without parsing the status response.
+pub fn get_server_latency(conf: &Conf) -> Result<Duration, MspErr> {
+    let (mut socket, _, compression) = request_server_status(conf)?;
+    let ping = get_server_ping(&mut socket, compression)?;
+
+    close_tcp_socket(&socket, conf);
+
+    Ok(Duration::from_millis(ping))
+}
+
+/// Async version of [get_server_latency].
+#[cfg(feature = "tokio")]
+pub async fn get_server_latency_async(conf: &Conf) -> Result<Duration, MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        let (mut socket, _, compression) = request_server_status_async(conf).await?;
+        let ping = get_server_ping_async(&mut socket, compression, conf).await?;
+
+        close_tcp_socket_async(&mut socket, conf).await;
+
+        Ok(Duration::from_millis(ping))
+    })
+    .await
+}
+
+/// Complete the handshake and status request, without parsing the status response.
+pub fn check_server_online(conf: &Conf) -> Result<(), MspErr> {
+    request_server_status(conf).map(|_| ())
+}
+
+/// Async version of [check_server_online].
+#[cfg(feature = "tokio")]
+pub async fn check_server_online_async(conf: &Conf) -> Result<(), MspErr> {
+    use crate::share::overall_timeout_async;
+
+    overall_timeout_async(conf, async {
+        request_server_status_async(conf).await.map(|_| ())
+    })
+    .await
+}
+
+/// Send handshake and status request, return the socket, the JSON buffers
+/// of the status response and whether compression is enabled.
+fn request_server_status(conf: &Conf) -> Result<(DeadlineTcpStream, Vec<u8>, bool), MspErr> {
+    let mut socket = create_tcp_socket(conf)?;
+    let (data_buffer, compression) = request_server_status_over(&mut socket, conf)?;
+
+    Ok((socket, data_buffer, compression))
+}
+
+/// Same as [request_server_status], over an already connected stream.
+fn request_server_status_over<S: PacketStream>(
+    stream: &mut S,
+    conf: &Conf,
+) -> Result<(Vec<u8>, bool), MspErr> {
+    stream.write_all(&build_handshake_packet(conf))?;
+    stream.write_all(&build_status_request_packet())?;
+
+    // Large responses (long plugin lists, big favicons) usually arrive in multiple reads,
+    // so every packet is read until its declared length is actually consumed.
+    let mut compression = false;
+
+    loop {
+        let (id, data) = read_packet(stream, compression)?;
+
+        if let Some(data_buffer) = process_status_packet(id, data, &mut compression)? {
+            return Ok((data_buffer, compression));
+        }
+    }
+}
+
+/// Async version of [request_server_status].
+#[cfg(feature = "tokio")]
+async fn request_server_status_async(
+    conf: &Conf,
+) -> Result<(tokio::net::TcpStream, Vec<u8>, bool), MspErr> {
+    use crate::share::{create_tcp_socket_async, timeout_async};
+    use tokio::io::AsyncWriteExt;
+
+    let read_time_out = conf.socket_conf.read_time_out;
+    let write_timeout = conf.socket_conf.write_timeout;
+    let mut socket = create_tcp_socket_async(conf).await?;
+    let hand_shake_packet = build_handshake_packet(conf);
     let status_request_packet = build_status_request_packet();
 
-    socket.write(&hand_shake_packet)?;
-    socket.write(&status_request_packet)?;
+    timeout_async(write_timeout, socket.write_all(&hand_shake_packet)).await?;
+    timeout_async(write_timeout, socket.write_all(&status_request_packet)).await?;
 
-    // Decode data or data size from response buffers
-    //
-    // TODO Packet cannot be larger than 2^21, therefore,
-    // it is necessary to handle the case of multiple packets.
-    let (_p_buf_len, _p_size) = decode_varint_from_socket(&mut socket)?;
-    let (_id_buf_len, _id) = decode_varint_from_socket(&mut socket)?;
-    let (_d_buf_len, d_size) = decode_varint_from_socket(&mut socket)?;
+    let mut compression = false;
+    let data_buffer = loop {
+        let (id, data) =
+            timeout_async(read_time_out, read_packet_async(&mut socket, compression)).await?;
+
+        if let Some(data_buffer) = process_status_packet(id, data, &mut compression)? {
+            break data_buffer;
+        }
+    };
 
-    let mut data_buffer = Vec::new();
+    Ok((socket, data_buffer, compression))
+}
 
-    Read::by_ref(&mut socket)
-        .take(d_size as u64)
-        .read_to_end(&mut data_buffer)?;
+/// Read a whole [packet](https://wiki.vg/Protocol#Packet_format) from socket.
+///
+/// Return packet id and packet data. Once `compression` is enabled by a Set Compression
+/// packet, the [compressed packet format](https://wiki.vg/Protocol#With_compression) is used.
+fn read_packet<S: Read>(socket: &mut S, compression: bool) -> Result<(i32, Vec<u8>), MspErr> {
+    let (_p_buf_len, p_size) = decode_varint_from_reader(socket)?;
+    let mut bufs = vec![0u8; check_packet_size(p_size)?];
 
-    // Debug block
-    //
-    // let mut demo_result_file = std::fs::File::create(".demo.json").expect("fail");
-    // demo_result_file.write(&data_buffer).unwrap();
-    // println!("{:?}", std::str::from_utf8(&data_buffer));
-
-    match std::str::from_utf8(&data_buffer) {
-        Ok(str) => match serde_json::from_str::<Server>(str) {
-            Ok(mut server) => {
-                // Get server ping
-                let ping = get_server_ping(&mut socket)?;
-
-                server.ping = ping;
-                Ok(server)
+    socket.read_exact(&mut bufs)?;
+
+    process_packet(bufs, compression)
+}
+
+/// Async version of [read_packet].
+#[cfg(feature = "tokio")]
+async fn read_packet_async(
+    socket: &mut tokio::net::TcpStream,
+    compression: bool,
+) -> Result<(i32, Vec<u8>), MspErr> {
+    use crate::varint::decode_varint_from_async_socket;
+    use tokio::io::AsyncReadExt;
+
+    let (_p_buf_len, p_size) = decode_varint_from_async_socket(socket).await?;
+    let mut bufs = vec![0u8; check_packet_size(p_size)?];
+
+    socket.read_exact(&mut bufs).await?;
+
+    process_packet(bufs, compression)
+}
+
+/// Packet cannot be larger than 2^21 - 1 bytes, see [packet format](https://wiki.vg/Protocol#Packet_format).
+fn check_packet_size(size: i32) -> Result<usize, MspErr> {
+    match size {
+        0..=MAX_PACKET_SIZE => Ok(size as usize),
+        _ => Err(MspErr::DataErr(format!(
+            "Invalid packet size: {}, expected between 0 and {}",
+            size, MAX_PACKET_SIZE
+        ))),
+    }
+}
+
+/// Split packet buffers (without the length prefix) into packet id and data,
+/// inflating the zlib-compressed payload if needed.
+fn process_packet(bufs: Vec<u8>, compression: bool) -> Result<(i32, Vec<u8>), MspErr> {
+    let bufs = match compression {
+        true => {
+            // Data length is 0 when the packet is smaller than the compression threshold.
+            let (d_buf_len, d_size) = decode_varint_from_bufs(&bufs)?;
+
+            match d_size {
+                0 => bufs[d_buf_len..].to_vec(),
+                _ => {
+                    let d_size = check_packet_size(d_size)?;
+                    let mut data = Vec::with_capacity(d_size);
+
+                    // Stop one byte past the declared size, so a zlib bomb cannot exhaust memory.
+                    ZlibDecoder::new(&bufs[d_buf_len..])
+                        .take(d_size as u64 + 1)
+                        .read_to_end(&mut data)?;
+
+                    if data.len() != d_size {
+                        return Err(MspErr::DataErr(format!(
+                            "Decompressed packet size mismatch, expected: {}, but got: {}",
+                            d_size,
+                            data.len()
+                        )));
+                    }
+
+                    data
+                }
+            }
+        }
+        false => bufs,
+    };
+    let (id_buf_len, id) = decode_varint_from_bufs(&bufs)?;
+
+    Ok((id, bufs[id_buf_len..].to_vec()))
+}
+
+/// Handle a packet received while waiting for the status response.
+///
+/// Return the JSON buffers of the status response, or `None` if the packet was
+/// a Set Compression packet and the response is still to come.
+fn process_status_packet(
+    id: i32,
+    data: Vec<u8>,
+    compression: &mut bool,
+) -> Result<Option<Vec<u8>>, MspErr> {
+    match id {
+        STATUS_RESPONSE_ID => {
+            let (d_buf_len, d_size) = decode_varint_from_bufs(&data)?;
+
+            match data.get(d_buf_len..d_buf_len + check_packet_size(d_size)?) {
+                Some(json) => Ok(Some(json.to_vec())),
+                None => Err(MspErr::DataErr(format!(
+                    "Incomplete status response, expected {} bytes, but got: {}",
+                    d_size,
+                    data.len() - d_buf_len
+                ))),
             }
-            Err(err) => Err(MspErr::DataErr(err.to_string())),
-        },
-        Err(err) => {
-            return Err(MspErr::InternalErr(err.to_string()));
         }
+        SET_COMPRESSION_ID => {
+            // The threshold only matters for packets sent by the client, which are always
+            // smaller than it here, so they are sent as uncompressed.
+            *compression = true;
+
+            Ok(None)
+        }
+        _ => Err(MspErr::DataErr(format!(
+            "Unexpected packet id: 0x{:02X} while waiting for status response",
+            id
+        ))),
     }
 }
 
@@ -201,20 +947,29 @@ pub fn get_server_status(conf: &Conf) -> Result<Server, MspErr> {
 fn build_handshake_packet(conf: &Conf) -> Vec<u8> {
     let mut packet = Vec::<u8>::new();
     let mut packet_data = Vec::<u8>::new();
-    let mut server_addr_bytes = conf.host.as_bytes().to_vec();
+    let mut server_addr_bytes = conf
+        .handshake_host
+        .as_deref()
+        .unwrap_or(&conf.host)
+        .as_bytes()
+        .to_vec();
 
     // See protocol version [numbers](https://wiki.vg/Protocol_version_numbers).
     //
     // If the client is pinging to determine what version to use,
     // by convention -1 should be set.
-    packet_data.append(&mut encode_varint(-1));
+    packet_data.append(&mut encode_varint(
+        conf.protocol_version.unwrap_or(DEFAULT_PROTOCOL_VERSION),
+    ));
     // Server address
     //
     // UTF-8 string prefixed with its size in bytes as a VarInt.
     packet_data.append(&mut encode_varint(server_addr_bytes.len() as i32));
     packet_data.append(&mut server_addr_bytes);
     // Server port
-    packet_data.append(&mut DEFAULT_SERVER_PORT.to_be_bytes().to_vec());
+    //
+    // Proxies such as BungeeCord and Velocity may route by it, so send the port actually connected to.
+    packet_data.append(&mut conf.port.to_be_bytes().to_vec());
     // Next state, should be 1 for status, but could also be 2 for login.
     packet_data.append(&mut encode_varint(1));
 
@@ -238,30 +993,172 @@ fn build_status_request_packet() -> Vec<u8> {
 }
 
 /// Build ping request packet buffer.
-fn build_ping_request_packet() -> Result<(u64, Vec<u8>), MspErr> {
+///
+/// If `compression` is enabled, the packet is sent in compressed packet format,
+/// with data length 0 for an uncompressed payload.
+fn build_ping_request_packet(compression: bool) -> Result<(u64, Vec<u8>), MspErr> {
     let mut packet = Vec::<u8>::new();
     let now_millis = get_server_current_time()?;
 
-    packet.append(&mut encode_varint(9));
+    match compression {
+        true => {
+            packet.append(&mut encode_varint(10));
+            packet.push(0x00);
+        }
+        false => packet.append(&mut encode_varint(9)),
+    }
     packet.push(0x01);
     packet.append(&mut now_millis.to_be_bytes().to_vec());
 
     Ok((now_millis, packet))
 }
 
-fn get_server_ping(socket: &mut TcpStream) -> Result<u64, MspErr> {
-    let (req_t, ping_request_packet) = build_ping_request_packet()?;
-    let mut time_bytes = [0u8; 8];
+fn get_server_ping<S: PacketStream>(socket: &mut S, compression: bool) -> Result<u64, MspErr> {
+    let (req_t, ping_request_packet) = build_ping_request_packet(compression)?;
+
+    socket.write_all(&ping_request_packet)?;
+
+    let (id, data) = read_packet(socket, compression)?;
+
+    check_ping_response(req_t, id, &data)
+}
+
+/// Async version of [get_server_ping].
+#[cfg(feature = "tokio")]
+async fn get_server_ping_async(
+    socket: &mut tokio::net::TcpStream,
+    compression: bool,
+    conf: &Conf,
+) -> Result<u64, MspErr> {
+    use crate::share::timeout_async;
+    use tokio::io::AsyncWriteExt;
+
+    let (req_t, ping_request_packet) = build_ping_request_packet(compression)?;
+
+    timeout_async(
+        conf.socket_conf.write_timeout,
+        socket.write_all(&ping_request_packet),
+    )
+    .await?;
+
+    let (id, data) = timeout_async(
+        conf.socket_conf.read_time_out,
+        read_packet_async(socket, compression),
+    )
+    .await?;
+
+    check_ping_response(req_t, id, &data)
+}
+
+/// Parse status response data into [Server], see [Conf::lenient].
+fn process_server_bufs_for(bufs: &[u8], conf: &Conf) -> Result<Server, MspErr> {
+    match conf.lenient {
+        true => process_server_bufs_lenient(bufs),
+        false => process_server_bufs(bufs),
+    }
+}
+
+/// Parse status response data into [Server].
+fn process_server_bufs(bufs: &[u8]) -> Result<Server, MspErr> {
+    // Parse the text directly rather than the untyped JSON, to keep the line and column of errors.
+    Ok(serde_json::from_str::<Server>(status_response_str(bufs)?)?)
+}
+
+/// Same as [process_server_bufs], but the fields that do not match [Server] are left out
+/// and reported in [Server::errors]. Invalid JSON still fails.
+fn process_server_bufs_lenient(bufs: &[u8]) -> Result<Server, MspErr> {
+    let str = status_response_str(bufs)?;
+    let err = match serde_json::from_str::<Server>(str) {
+        Ok(server) => return Ok(server),
+        Err(err) if err.is_data() => err,
+        Err(err) => return Err(err.into()),
+    };
+    let fields = match serde_json::from_str::<serde_json::Value>(str)? {
+        serde_json::Value::Object(fields) => fields,
+        _ => return Err(err.into()),
+    };
+    let base = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(LENIENT_BASE)?;
+    let mut status = base.clone();
+    let mut errors = Vec::new();
+
+    for key in base.keys().filter(|key| !fields.contains_key(*key)) {
+        errors.push(MspErr::DataErr(format!("Missing field {}", key)));
+    }
+
+    // Check each field on its own, so that an error is attributed to the right one.
+    for (key, value) in fields {
+        let mut single = base.clone();
+
+        single.insert(key.clone(), value.clone());
+
+        match serde_json::from_value::<Server>(serde_json::Value::Object(single)) {
+            Ok(_) => {
+                status.insert(key, value);
+            }
+            Err(err) => errors.push(MspErr::DataErr(format!("Cannot parse {}: {}", key, err))),
+        }
+    }
+
+    let mut server = serde_json::from_value::<Server>(serde_json::Value::Object(status))?;
+
+    server.errors = errors;
+
+    Ok(server)
+}
+
+/// Parse only the player counts from status response data, other fields
+/// (e.g. the favicon) are skipped without being allocated.
+fn process_player_counts_bufs(bufs: &[u8]) -> Result<(i32, i32), MspErr> {
+    #[derive(Deserialize)]
+    struct PlayerCounts {
+        #[serde(default)]
+        players: Option<Counts>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    struct Counts {
+        online: i32,
+        max: i32,
+    }
+
+    let counts = serde_json::from_slice::<PlayerCounts>(bufs)?
+        .players
+        .unwrap_or_default();
+
+    Ok((counts.online, counts.max))
+}
 
-    socket.write(&ping_request_packet)?;
-    decode_varint_from_socket(socket)?;
-    decode_varint_from_socket(socket)?;
+/// Parse status response data into untyped JSON.
+fn process_server_raw_bufs(bufs: &[u8]) -> Result<serde_json::Value, MspErr> {
+    Ok(serde_json::from_str::<serde_json::Value>(
+        status_response_str(bufs)?,
+    )?)
+}
+
+fn status_response_str(bufs: &[u8]) -> Result<&str, MspErr> {
+    let str = std::str::from_utf8(bufs).map_err(|err| MspErr::InternalErr(err.to_string()))?;
+
+    log::debug!("Server status response: {}", str);
 
-    // Why  take 8 buffers?
+    Ok(str)
+}
+
+/// Check the echoed ping response and calculate the latency.
+fn check_ping_response(req_t: u64, id: i32, data: &[u8]) -> Result<u64, MspErr> {
+    // Why take 8 buffers?
     //
     // Because server should response the same as sent by the client.
-    Read::by_ref(socket).take(8).read(&mut time_bytes)?;
-
+    let time_bytes: [u8; 8] = match (id, data.try_into()) {
+        (PONG_RESPONSE_ID, Ok(time_bytes)) => time_bytes,
+        _ => {
+            return Err(MspErr::DataErr(format!(
+                "Invalid pong response, packet id: 0x{:02X}, data length: {}",
+                id,
+                data.len()
+            )));
+        }
+    };
     let receive_t = u64::from_be_bytes(time_bytes);
 
     if receive_t == req_t {
@@ -282,3 +1179,472 @@ fn enforces_secure_chat_default() -> bool {
 fn ping_default() -> u64 {
     0
 }
+
+#[cfg(test)]
+mod regular_server_test {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::{
+        io::{Cursor, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    /// Stream replaying recorded server bytes, keeping what the client sends.
+    struct RecordedStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for RecordedStream {
+        fn read(&mut self, bufs: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(bufs)
+        }
+    }
+
+    impl Write for RecordedStream {
+        fn write(&mut self, bufs: &[u8]) -> std::io::Result<usize> {
+            self.output.write(bufs)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn frame(bufs: &[u8]) -> Vec<u8> {
+        let mut packet = encode_varint(bufs.len() as i32);
+
+        packet.extend_from_slice(bufs);
+        packet
+    }
+
+    /// Mock server sending the responses in small chunks.
+    fn serve(compression: bool) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            // Requests are shorter than 128 bytes, so the length takes one byte.
+            let read_request = |socket: &mut TcpStream| {
+                let mut p_size = [0u8; 1];
+
+                socket.read_exact(&mut p_size).ok()?;
+
+                let mut bufs = vec![0u8; p_size[0] as usize];
+
+                socket.read_exact(&mut bufs).ok()?;
+                Some(bufs)
+            };
+
+            // Handshake and status request.
+            read_request(&mut socket).unwrap();
+            read_request(&mut socket).unwrap();
+
+            let json = br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":0},"description":{"text":"Chunked"},"favicon":""}"#;
+            let mut status = vec![STATUS_RESPONSE_ID as u8];
+            let mut response = Vec::new();
+
+            status.append(&mut encode_varint(json.len() as i32));
+            status.extend_from_slice(json);
+
+            match compression {
+                true => {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                    let mut data = encode_varint(status.len() as i32);
+
+                    encoder.write_all(&status).unwrap();
+                    data.append(&mut encoder.finish().unwrap());
+                    // Set Compression with threshold 256.
+                    response.append(&mut frame(&[SET_COMPRESSION_ID as u8, 0x80, 0x02]));
+                    response.append(&mut frame(&data));
+                }
+                false => response.append(&mut frame(&status)),
+            }
+
+            for chunk in response.chunks(7) {
+                socket.write_all(chunk).unwrap();
+                socket.flush().unwrap();
+                thread::sleep(Duration::from_millis(2));
+            }
+
+            // Echo the ping request as pong response, if the client asks for it.
+            if let Some(ping) = read_request(&mut socket) {
+                socket.write_all(&frame(&ping)).unwrap();
+            }
+        });
+
+        port
+    }
+
+    #[test]
+    fn test_process_server_bufs_unknown_fields() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"Velocity 3.2.0","protocol":763,"extra":1},"players":{"max":20,"online":1,"sample":[{"name":"Notch","id":"069a79f4-44e9-4726-a5be-fca90e38aaf5","extra":true}]},"description":{"text":""},"favicon":"","previewsChat":false}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.version.name, "Velocity 3.2.0");
+        assert_eq!(server.players.sample[0].name, "Notch");
+        assert!(!server.players.is_sample_truncated());
+    }
+
+    #[test]
+    fn test_process_server_bufs_lenient() {
+        let bufs = br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":"20","online":1},"description":{"text":"A Minecraft Server"},"favicon":42}"#;
+
+        assert!(process_server_bufs(bufs).is_err());
+
+        let server = process_server_bufs_lenient(bufs).unwrap();
+
+        assert_eq!(server.version.protocol, 763);
+        assert_eq!(server.description.text, "A Minecraft Server");
+        assert_eq!(server.players.max, 0);
+        assert_eq!(server.favicon, "");
+        assert_eq!(server.errors.len(), 2);
+
+        let server = process_server_bufs_lenient(br#"{"description":[]}"#).unwrap();
+
+        assert_eq!(server.version.protocol, -1);
+        assert_eq!(server.errors.len(), 2);
+        assert!(process_server_bufs_lenient(b"{").is_err());
+    }
+
+    #[test]
+    fn test_server_eq_ignores_ping() {
+        let bufs = br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":1},"description":"A Minecraft Server"}"#;
+        let server = process_server_bufs(bufs).unwrap();
+        let mut other = server.clone();
+
+        other.ping = 42;
+        other.addr = Some(([127, 0, 0, 1], 25565).into());
+        assert_eq!(server, other);
+
+        other.players.online = 2;
+        assert_ne!(server, other);
+    }
+
+    #[test]
+    fn test_process_server_bufs_without_favicon() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":0},"description":{"text":"A Minecraft Server"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.favicon, "");
+        assert!(server.favicon_png().unwrap().is_empty());
+        assert!(server.favicon_dimensions().is_err());
+    }
+
+    #[test]
+    fn test_process_server_bufs_string_description() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":0},"description":"A Minecraft Server"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.description.text, "A Minecraft Server");
+        assert!(server.description.extra.is_empty());
+    }
+
+    #[test]
+    fn test_process_server_bufs_modern_components() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.21.5","protocol":770},"players":{"max":20,"online":0},"description":{"text":"","extra":[{"text":"Shop","font":"minecraft:uniform","insertion":"/shop","click_event":{"action":"run_command","command":"/shop"}},{"text":"Info","hoverEvent":{"action":"show_text","contents":"Hi"}}]}}"#,
+        )
+        .unwrap();
+        let extra = &server.description.extra;
+
+        assert_eq!(extra[0].font.as_deref(), Some("minecraft:uniform"));
+        assert_eq!(extra[0].insertion.as_deref(), Some("/shop"));
+        assert_eq!(extra[0].click_event.as_ref().unwrap()["command"], "/shop");
+        assert_eq!(extra[1].hover_event.as_ref().unwrap()["contents"], "Hi");
+        assert!(!server.to_json(false).unwrap().contains("\"font\":null"));
+    }
+
+    #[test]
+    fn test_process_server_bufs_unmodeled_component_keys() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":0},"description":{"text":"A ","color":"gold","bold":true,"hoverEvent":{"action":"show_text","contents":"Hi"},"extra":[{"translate":"menu.server","with":["x"],"shadow_color":-16777216},{"keybind":"key.jump"}]}}"#,
+        )
+        .unwrap();
+        let description = &server.description;
+
+        assert_eq!(description.color, "gold");
+        assert!(description.bold);
+        assert!(description.hover_event.is_some());
+        assert_eq!(description.extra[0].other["translate"], "menu.server");
+        assert_eq!(description.extra[0].other["shadow_color"], -16777216);
+        assert_eq!(description.extra[1].other["keybind"], "key.jump");
+        assert_eq!(description.to_plain(), "A ");
+        assert!(description.to_ansi().starts_with("\x1b[0m\x1b[33;1mA "));
+
+        let json = server.to_json(false).unwrap();
+
+        assert!(json.contains(r#""color":"gold""#));
+        assert!(json.contains(r#""keybind":"key.jump""#));
+    }
+
+    #[test]
+    fn test_players_sample_placeholder() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":100,"online":30,"sample":[{"name":"\u00a7aWelcome!","id":"00000000-0000-0000-0000-000000000000"},{"name":"Notch","id":"069a79f4-44e9-4726-a5be-fca90e38aaf5"}]},"description":{"text":""},"favicon":""}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.players.sample.len(), 1);
+        assert_eq!(server.players.sample[0].name, "Notch");
+        assert!(server.players.is_sample_truncated());
+    }
+
+    #[test]
+    fn test_process_player_counts_bufs() {
+        assert_eq!(
+            process_player_counts_bufs(
+                br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":3,"sample":[]},"description":"A Minecraft Server","favicon":"data:image/png;base64,AAAA"}"#,
+            )
+            .unwrap(),
+            (3, 20)
+        );
+    }
+
+    #[test]
+    fn test_favicon_dimensions() {
+        let mut server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":3},"description":"","favicon":"data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAEAAAABACAYAAA=="}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.favicon_dimensions().unwrap(), (64, 64));
+
+        server.favicon = "data:image/png;base64,AAAA".into();
+        assert!(server.favicon_dimensions().is_err());
+    }
+
+    #[test]
+    fn test_server_summary() {
+        let mut server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":3},"description":{"text":"\u00a7aA Minecraft\n  Server"},"favicon":""}"#,
+        )
+        .unwrap();
+
+        server.ping = 42;
+
+        assert_eq!(
+            server.summary(),
+            "1.20.1 | 3/20 players | A Minecraft Server | 42ms"
+        );
+        assert!(!server.to_json(false).unwrap().contains('\n'));
+        assert_eq!(server.to_json(true).unwrap(), server.to_string());
+        assert!(!server.is_modded());
+    }
+
+    #[test]
+    fn test_server_mod_count() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.12.2","protocol":340},"players":{"max":20,"online":0},"description":"","forgeData":{"mods":[{"modId":"minecraft","modmarker":"1.12.2"},{"modId":"forge","modmarker":"14.23.5.2860"}],"channels":[]}}"#,
+        )
+        .unwrap();
+
+        assert!(server.is_modded());
+        assert_eq!(server.mod_count(), 2);
+    }
+
+    #[test]
+    fn test_process_server_bufs_null_players() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":null,"description":""}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.players.max, 0);
+
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":1,"sample":null},"description":""}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.players.online, 1);
+        assert!(server.players.sample.is_empty());
+        assert!(process_server_bufs(
+            br#"{"version":{"name":"1.20.1","protocol":763},"description":""}"#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_process_server_bufs_invalid_json() {
+        match process_server_bufs(b"{\n  \"version\": }") {
+            Err(MspErr::JsonErr(err)) => assert_eq!((err.line(), err.column()), (2, 14)),
+            result => panic!("Expected JsonErr, but got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_process_server_bufs_chat_fields() {
+        let server = process_server_bufs(
+            br#"{"version":{"name":"1.19.2","protocol":760},"players":{"max":20,"online":0},"description":"","previewsChat":true,"preventsChatReports":true}"#,
+        )
+        .unwrap();
+
+        assert_eq!(server.previews_chat, Some(true));
+        assert_eq!(server.prevents_chat_reports, Some(true));
+        assert!(server
+            .to_json(false)
+            .unwrap()
+            .contains(r#""previewsChat":true"#));
+    }
+
+    #[test]
+    fn test_process_packet_inflated_size() {
+        let compressed = |declared: usize, len: usize| {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            let mut bufs = encode_varint(declared as i32);
+
+            encoder.write_all(&vec![0u8; len]).unwrap();
+            bufs.append(&mut encoder.finish().unwrap());
+            bufs
+        };
+
+        assert_eq!(
+            process_packet(compressed(300, 300), true).unwrap().1.len(),
+            299
+        );
+        assert!(process_packet(compressed(300, 299), true).is_err());
+        // Inflates far beyond the declared size, only one extra byte is read.
+        assert!(process_packet(compressed(300, 4 * 1024 * 1024), true).is_err());
+    }
+
+    #[test]
+    fn test_build_handshake_packet() {
+        let packet = build_handshake_packet(&Conf::create_with_port("localhost", 25570));
+
+        assert_eq!(
+            packet,
+            vec![
+                19, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 9, b'l', b'o', b'c', b'a', b'l', b'h',
+                b'o', b's', b't', 0x63, 0xE2, 0x01
+            ]
+        );
+
+        // Connect to one host, but hand shake with another, as a Forge client.
+        let conf = Conf::builder()
+            .host("127.0.0.1")
+            .handshake_host("mc.example.com\0FML3\0")
+            .build();
+        let packet = build_handshake_packet(&conf);
+
+        assert_eq!(&packet[7..8], &[20]);
+        assert_eq!(&packet[8..28], b"mc.example.com\0FML3\0");
+    }
+
+    #[test]
+    fn test_get_server_status_over_recorded_stream() {
+        let json = br#"{"version":{"name":"1.20.1","protocol":763},"players":{"max":20,"online":3},"description":"Recorded"}"#;
+        let mut status = vec![STATUS_RESPONSE_ID as u8];
+
+        status.append(&mut encode_varint(json.len() as i32));
+        status.extend_from_slice(json);
+
+        let mut stream = RecordedStream {
+            input: Cursor::new(frame(&status)),
+            output: Vec::new(),
+        };
+        let mut conf = Conf::create_with_port("localhost", 25565);
+
+        conf.socket_conf.measure_ping = false;
+
+        let server = get_server_status_over(&mut stream, &conf).unwrap();
+
+        assert_eq!(server.players.online, 3);
+        assert_eq!(server.description.to_plain(), "Recorded");
+        assert_eq!(
+            stream.output,
+            [build_handshake_packet(&conf), build_status_request_packet()].concat()
+        );
+
+        // The server closes the connection without a status response.
+        let mut stream = RecordedStream {
+            input: Cursor::new(vec![]),
+            output: Vec::new(),
+        };
+
+        assert!(get_server_status_over(&mut stream, &conf).is_err());
+    }
+
+    #[test]
+    fn test_get_server_status_chunked() {
+        for compression in [false, true] {
+            let conf = Conf::create_with_port("127.0.0.1", serve(compression));
+            let server = get_server_status(&conf).unwrap();
+
+            assert_eq!(server.version.protocol, 763);
+            assert_eq!(server.description.text, "Chunked");
+            assert_eq!(server.addr, Some(([127, 0, 0, 1], conf.port).into()));
+        }
+    }
+
+    #[test]
+    fn test_get_server_status_bytes() {
+        let bytes = get_server_status_bytes(&Conf::create_with_port("127.0.0.1", serve(true)));
+
+        assert!(bytes
+            .unwrap()
+            .starts_with(br#"{"version":{"name":"1.20.1""#));
+    }
+
+    #[test]
+    fn test_get_server_status_on() {
+        let port = serve(false);
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let server = get_server_status_on(stream, "mc.example.com", 25565).unwrap();
+
+        assert_eq!(server.description.text, "Chunked");
+        assert_eq!(server.addr, Some(([127, 0, 0, 1], port).into()));
+    }
+
+    #[test]
+    fn test_get_server_latency() {
+        let conf = Conf::create_with_port("127.0.0.1", serve(true));
+
+        assert!(get_server_latency(&conf).is_ok());
+    }
+
+    #[test]
+    fn test_is_online() {
+        let closed_port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        assert!(Conf::create_with_port("127.0.0.1", serve(false)).is_online());
+        assert!(!Conf::create_with_port("127.0.0.1", closed_port).is_online());
+    }
+
+    #[test]
+    fn test_overall_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut conf = Conf::create_with_port("127.0.0.1", listener.local_addr().unwrap().port());
+
+        // Trickle one byte of a huge packet every 20ms, each read is well within the socket timeout.
+        thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+
+            socket.write_all(&[0xFF, 0xFF, 0x7F]).unwrap();
+
+            while socket.write_all(&[0x00]).is_ok() {
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        conf.socket_conf.overall_timeout = Some(Duration::from_millis(200));
+
+        match get_server_status(&conf) {
+            Err(MspErr::Timeout(err)) => assert_eq!(err.kind(), std::io::ErrorKind::TimedOut),
+            result => panic!("Expected timeout, but got: {:?}", result),
+        }
+    }
+}
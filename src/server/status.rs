@@ -0,0 +1,146 @@
+use super::{BedrockServer, LegacyBetaServer, LegacyServer, Server, ServerKind};
+
+/// Fields shared by the server info of every protocol,
+/// for code that handles any of them uniformly.
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{Conf, MspErr, ServerStatus};
+///
+/// fn print_status(status: &dyn ServerStatus) {
+///     println!(
+///         "{} ({}/{}) {}",
+///         status.version_name(),
+///         status.online_players(),
+///         status.max_players(),
+///         status.motd_plain()
+///     );
+/// }
+///
+/// fn main() -> Result<(), MspErr> {
+///     print_status(&*Conf::create("www.example.com").get_status_auto()?);
+///
+///     Ok(())
+/// }
+/// ```
+pub trait ServerStatus {
+    /// MOTD without formatting codes.
+    fn motd_plain(&self) -> String;
+    /// Online players.
+    fn online_players(&self) -> i32;
+    /// Max players.
+    fn max_players(&self) -> i32;
+    /// Version name, empty if the protocol does not report one.
+    fn version_name(&self) -> String;
+}
+
+impl ServerStatus for Server {
+    fn motd_plain(&self) -> String {
+        self.description.to_plain()
+    }
+
+    fn online_players(&self) -> i32 {
+        self.players.online
+    }
+
+    fn max_players(&self) -> i32 {
+        self.players.max
+    }
+
+    fn version_name(&self) -> String {
+        self.version.name.clone()
+    }
+}
+
+/// Also covers [NettyServer](super::NettyServer).
+impl ServerStatus for LegacyServer {
+    fn motd_plain(&self) -> String {
+        self.description().to_plain()
+    }
+
+    fn online_players(&self) -> i32 {
+        self.online_players
+    }
+
+    fn max_players(&self) -> i32 {
+        self.max_players
+    }
+
+    fn version_name(&self) -> String {
+        self.server_version.clone()
+    }
+}
+
+impl ServerStatus for LegacyBetaServer {
+    fn motd_plain(&self) -> String {
+        self.description().to_plain()
+    }
+
+    fn online_players(&self) -> i32 {
+        self.online_players
+    }
+
+    fn max_players(&self) -> i32 {
+        self.max_players
+    }
+
+    /// Beta servers do not report their version.
+    fn version_name(&self) -> String {
+        String::new()
+    }
+}
+
+impl ServerStatus for BedrockServer {
+    fn motd_plain(&self) -> String {
+        BedrockServer::motd_plain(self)
+    }
+
+    fn online_players(&self) -> i32 {
+        self.online_players
+    }
+
+    fn max_players(&self) -> i32 {
+        self.max_players
+    }
+
+    fn version_name(&self) -> String {
+        self.version_name.clone()
+    }
+}
+
+impl std::ops::Deref for ServerKind {
+    type Target = dyn ServerStatus;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ServerKind::Regular(server) => server,
+            ServerKind::Netty(server) | ServerKind::Legacy(server) => server,
+            ServerKind::LegacyBeta(server) => server,
+            ServerKind::Bedrock(server) => server,
+        }
+    }
+}
+
+#[cfg(test)]
+mod status_test {
+    use super::*;
+
+    #[test]
+    fn test_server_kind_deref() {
+        let kind = ServerKind::Legacy(LegacyServer {
+            protocol_version: 61,
+            protocol_version_raw: "61".into(),
+            server_version: "1.5.2".into(),
+            motd: "§aA Minecraft Server".into(),
+            online_players: 3,
+            max_players: 20,
+            beta_format: false,
+        });
+
+        assert_eq!(kind.motd_plain(), "A Minecraft Server");
+        assert_eq!(kind.online_players(), 3);
+        assert_eq!(kind.max_players(), 20);
+        assert_eq!(kind.version_name(), "1.5.2");
+    }
+}
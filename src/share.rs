@@ -1,9 +1,15 @@
-use crate::{conf::Conf, MspErr, SocketConf};
+use crate::{conf::Conf, socks::socks5_connect, IpPreference, MspErr, SocketConf};
+#[cfg(feature = "tokio")]
+use std::future::Future;
 use std::{
-    net::{TcpStream, UdpSocket},
-    time::{SystemTime, UNIX_EPOCH},
+    io::{Read, Write},
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+/// The largest possible UDP payload, used as buffer size when receiving a whole datagram.
+pub const UDP_MAX_DATAGRAM_SIZE: usize = 65535;
+
 pub fn get_server_current_time() -> Result<u64, MspErr> {
     match SystemTime::now().duration_since(UNIX_EPOCH) {
         Ok(t) => {
@@ -26,17 +32,403 @@ pub fn get_server_current_time() -> Result<u64, MspErr> {
     }
 }
 
-pub fn create_tcp_socket(conf: &Conf) -> Result<TcpStream, MspErr> {
-    let socket = TcpStream::connect(conf)?;
+/// Whether the error is worth retrying, see [SocketConf::max_retries].
+fn is_transient_err(err: &MspErr) -> bool {
+    match err {
+        MspErr::Timeout(_) => true,
+        MspErr::IoErr(err) => matches!(
+            err.kind(),
+            std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted
+        ),
+        _ => false,
+    }
+}
+
+/// Delay before the given retry, doubled on each retry.
+fn retry_delay(socket_conf: &SocketConf, retry: u32) -> std::time::Duration {
+    socket_conf
+        .retry_backoff
+        .saturating_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+}
+
+/// Run the operation, retrying on transient socket errors with exponential backoff.
+pub fn retry<T, F>(socket_conf: &SocketConf, mut operation: F) -> Result<T, MspErr>
+where
+    F: FnMut() -> Result<T, MspErr>,
+{
+    let mut retry = 0;
+
+    loop {
+        match operation() {
+            Err(err) if retry < socket_conf.max_retries && is_transient_err(&err) => {
+                std::thread::sleep(retry_delay(socket_conf, retry));
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Async version of [retry].
+#[cfg(feature = "tokio")]
+pub async fn retry_async<T, F, Fut>(socket_conf: &SocketConf, mut operation: F) -> Result<T, MspErr>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, MspErr>>,
+{
+    let mut retry = 0;
+
+    loop {
+        match operation().await {
+            Err(err) if retry < socket_conf.max_retries && is_transient_err(&err) => {
+                tokio::time::sleep(retry_delay(socket_conf, retry)).await;
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Connect to the server, through [Conf::proxy] if set.
+///
+/// The whole exchange on the returned stream is bounded by [SocketConf::overall_timeout].
+pub fn create_tcp_socket(conf: &Conf) -> Result<DeadlineTcpStream, MspErr> {
+    let deadline = conf
+        .socket_conf
+        .overall_timeout
+        .map(|timeout| Instant::now() + timeout);
+    let socket = match &conf.proxy {
+        Some(proxy) => connect_tcp(
+            &resolve_host(
+                conf,
+                &proxy.host,
+                proxy.port,
+                conf.socket_conf.ip_preference,
+            )?,
+            deadline,
+            conf.socket_conf.connect_timeout,
+            conf.socket_conf.bind_addr,
+        )?,
+        None => connect_tcp(
+            &resolve_conf_addrs(conf, conf.socket_conf.ip_preference)?,
+            deadline,
+            conf.socket_conf.connect_timeout,
+            conf.socket_conf.bind_addr,
+        )?,
+    };
 
     socket.set_read_timeout(conf.socket_conf.read_time_out)?;
     socket.set_write_timeout(conf.socket_conf.write_timeout)?;
 
-    Ok(socket)
+    let mut stream = DeadlineTcpStream {
+        socket,
+        deadline,
+        read_time_out: conf.socket_conf.read_time_out,
+        write_timeout: conf.socket_conf.write_timeout,
+    };
+
+    if let Some(proxy) = &conf.proxy {
+        socks5_connect(&mut stream, proxy, &conf.host, conf.port)?;
+    }
+
+    Ok(stream)
+}
+
+/// Resolve the server host, unless its address is cached in [Conf::resolved_addr].
+pub fn resolve_conf_addrs(
+    conf: &Conf,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match conf.resolved_addr {
+        Some(addr) => Ok(vec![addr]),
+        None => resolve_host(conf, &conf.host, conf.port, preference),
+    }
+}
+
+/// Resolve the host with [Conf::resolver] if set, otherwise with [resolve_addrs].
+pub fn resolve_host(
+    conf: &Conf,
+    host: &str,
+    port: u16,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match &conf.resolver {
+        Some(resolver) => check_resolved_addrs(host, resolver.resolve(host, port)?, preference),
+        None => resolve_addrs(host, port, preference),
+    }
+}
+
+/// Resolve the host, failing with [MspErr::DnsErr] if it has no address.
+///
+/// Addresses are ordered according to `preference`.
+pub fn resolve_addrs(
+    host: &str,
+    port: u16,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) => check_resolved_addrs(host, addrs.collect(), preference),
+        Err(err) => Err(MspErr::DnsErr(format!(
+            "Cannot resolve host {}: {}",
+            host, err
+        ))),
+    }
+}
+
+fn check_resolved_addrs(
+    host: &str,
+    mut addrs: Vec<SocketAddr>,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    // Stable sort keeps the resolver order within the same family.
+    addrs.sort_by_key(|addr| match preference {
+        IpPreference::Resolver => false,
+        IpPreference::Ipv4 => addr.is_ipv6(),
+        IpPreference::Ipv6 => addr.is_ipv4(),
+    });
+
+    match addrs.is_empty() {
+        true => Err(MspErr::DnsErr(format!(
+            "Host {} does not resolve to any address.",
+            host
+        ))),
+        false => Ok(addrs),
+    }
+}
+
+fn connect_tcp(
+    addrs: &[SocketAddr],
+    deadline: Option<Instant>,
+    connect_timeout: Option<Duration>,
+    bind_addr: Option<SocketAddr>,
+) -> Result<TcpStream, MspErr> {
+    let mut last_err = None;
+
+    for addr in addrs {
+        if !is_same_family(bind_addr, addr) {
+            continue;
+        }
+
+        match connect_tcp_addr(
+            addr,
+            connect_time_limit(deadline, connect_timeout)?,
+            bind_addr,
+        ) {
+            Ok(socket) => return Ok(socket),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(match last_err {
+        Some(err) => err.into(),
+        None => no_addr_of_bind_family(bind_addr),
+    })
+}
+
+fn connect_tcp_addr(
+    addr: &SocketAddr,
+    timeout: Option<Duration>,
+    bind_addr: Option<SocketAddr>,
+) -> std::io::Result<TcpStream> {
+    let bind_addr = match bind_addr {
+        Some(bind_addr) => bind_addr,
+        None => {
+            return match timeout {
+                Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+        }
+    };
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(*addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+
+    socket.bind(&bind_addr.into())?;
+
+    match timeout {
+        Some(timeout) => socket.connect_timeout(&(*addr).into(), timeout)?,
+        None => socket.connect(&(*addr).into())?,
+    }
+
+    Ok(socket.into())
+}
+
+/// A socket bound to [SocketConf::bind_addr] can only connect to addresses of the same family.
+pub fn is_same_family(bind_addr: Option<SocketAddr>, addr: &SocketAddr) -> bool {
+    match bind_addr {
+        Some(bind_addr) => bind_addr.is_ipv4() == addr.is_ipv4(),
+        None => true,
+    }
+}
+
+/// Timeout of connecting to a single address, the shorter of [SocketConf::connect_timeout]
+/// and the time left before the deadline.
+fn connect_time_limit(
+    deadline: Option<Instant>,
+    connect_timeout: Option<Duration>,
+) -> std::io::Result<Option<Duration>> {
+    let remaining = match deadline {
+        Some(deadline) => Some(remaining_time(deadline)?),
+        None => None,
+    };
+
+    Ok(match (remaining, connect_timeout) {
+        (Some(remaining), Some(connect_timeout)) => Some(remaining.min(connect_timeout)),
+        (remaining, connect_timeout) => remaining.or(connect_timeout),
+    })
+}
+
+fn no_addr_of_bind_family(bind_addr: Option<SocketAddr>) -> MspErr {
+    MspErr::DataErr(format!(
+        "No resolved address of the same family as the bind address {:?}.",
+        bind_addr
+    ))
+}
+
+/// Time left before the deadline, fail with [std::io::ErrorKind::TimedOut] if it has passed.
+fn remaining_time(deadline: Instant) -> std::io::Result<Duration> {
+    match deadline.saturating_duration_since(Instant::now()) {
+        Duration::ZERO => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "Overall timeout of the status request exceeded",
+        )),
+        remaining => Ok(remaining),
+    }
+}
+
+/// Byte stream a TCP-based protocol is spoken over.
+///
+/// Implemented for anything that is [Read] and [Write], so the status requests can
+/// run over another transport than the TCP socket created by [Conf], such as a Unix
+/// domain socket or a recorded byte stream in tests.
+pub trait PacketStream: Read + Write {}
+
+impl<T: Read + Write> PacketStream for T {}
+
+/// [TcpStream] bounded by [SocketConf::overall_timeout].
+///
+/// Before every read and write, the socket timeout is shortened to the time left,
+/// so a server trickling bytes cannot keep the exchange alive past the deadline.
+pub struct DeadlineTcpStream {
+    socket: TcpStream,
+    deadline: Option<Instant>,
+    read_time_out: Option<Duration>,
+    write_timeout: Option<Duration>,
 }
 
+impl DeadlineTcpStream {
+    /// Address of the remote peer, see [TcpStream::peer_addr].
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Shut down the connection, see [TcpStream::shutdown].
+    pub fn shutdown(&self, how: std::net::Shutdown) -> std::io::Result<()> {
+        self.socket.shutdown(how)
+    }
+
+    /// Socket timeout limited to the time left, `None` if there is no deadline.
+    fn limit_timeout(&self, timeout: Option<Duration>) -> std::io::Result<Option<Duration>> {
+        match self.deadline {
+            Some(deadline) => {
+                let remaining = remaining_time(deadline)?;
+
+                Ok(Some(timeout.map_or(remaining, |t| t.min(remaining))))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Report the socket timeout caused by the deadline as [std::io::ErrorKind::TimedOut].
+    fn map_err(&self, err: std::io::Error) -> std::io::Error {
+        match (self.deadline, err.kind()) {
+            (Some(deadline), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                remaining_time(deadline).err().unwrap_or(err)
+            }
+            _ => err,
+        }
+    }
+}
+
+impl Read for DeadlineTcpStream {
+    fn read(&mut self, bufs: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(timeout) = self.limit_timeout(self.read_time_out)? {
+            self.socket.set_read_timeout(Some(timeout))?;
+        }
+
+        self.socket.read(bufs).map_err(|err| self.map_err(err))
+    }
+}
+
+impl Write for DeadlineTcpStream {
+    fn write(&mut self, bufs: &[u8]) -> std::io::Result<usize> {
+        if let Some(timeout) = self.limit_timeout(self.write_timeout)? {
+            self.socket.set_write_timeout(Some(timeout))?;
+        }
+
+        self.socket.write(bufs).map_err(|err| self.map_err(err))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.socket.flush()
+    }
+}
+
+/// UDP-based protocols cannot be tunneled through [Conf::proxy].
+pub fn check_udp_proxy(conf: &Conf) -> Result<(), MspErr> {
+    match conf.proxy {
+        Some(_) => Err(MspErr::NoImpl(
+            "Proxy is not supported for UDP-based protocols.".into(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Ports below 1024 are privileged on Unix, binding them requires root permission.
+const PRIVILEGED_PORT_LIMIT: u16 = 1024;
+
+/// Explain why binding the UDP socket failed if the port is privileged.
+fn map_udp_bind_err(port: u16, err: std::io::Error) -> MspErr {
+    match err.kind() {
+        std::io::ErrorKind::PermissionDenied if port != 0 && port < PRIVILEGED_PORT_LIMIT => {
+            MspErr::DataErr(format!(
+                "Cannot bind UDP socket to privileged port {}, use a port not less than {} for SocketConf::rep_udp_port: {}",
+                port, PRIVILEGED_PORT_LIMIT, err
+            ))
+        }
+        _ => err.into(),
+    }
+}
+
+/// Local address of the UDP socket, [SocketConf::rep_udp_ipv4] or [SocketConf::rep_udp_ipv6]
+/// with [SocketConf::rep_udp_port].
+fn udp_bind_addr(socket_conf: &SocketConf, ipv6: bool) -> SocketAddr {
+    let ip = match ipv6 {
+        true => IpAddr::V6(socket_conf.rep_udp_ipv6),
+        false => IpAddr::V4(socket_conf.rep_udp_ipv4),
+    };
+
+    SocketAddr::new(ip, socket_conf.rep_udp_port)
+}
+
+/// Create an IPv4 UDP socket, see [create_udp_socket_for].
 pub fn create_udp_socket(socket_conf: &SocketConf) -> Result<UdpSocket, MspErr> {
-    let socket = UdpSocket::bind((socket_conf.rep_udp_ipv4, socket_conf.rep_udp_port))?;
+    bind_udp_socket(socket_conf, false)
+}
+
+/// Create a UDP socket of the same address family as `target`.
+pub fn create_udp_socket_for(
+    socket_conf: &SocketConf,
+    target: &SocketAddr,
+) -> Result<UdpSocket, MspErr> {
+    bind_udp_socket(socket_conf, target.is_ipv6())
+}
+
+fn bind_udp_socket(socket_conf: &SocketConf, ipv6: bool) -> Result<UdpSocket, MspErr> {
+    let socket = UdpSocket::bind(udp_bind_addr(socket_conf, ipv6))
+        .map_err(|err| map_udp_bind_err(socket_conf.rep_udp_port, err))?;
 
     socket.set_read_timeout(socket_conf.read_time_out)?;
     socket.set_write_timeout(socket_conf.write_timeout)?;
@@ -44,6 +436,250 @@ pub fn create_udp_socket(socket_conf: &SocketConf) -> Result<UdpSocket, MspErr>
     Ok(socket)
 }
 
+/// Fail if a UDP datagram was not sent whole, `sent` being the length returned by `send`.
+pub fn check_datagram_sent(sent: usize, bufs: &[u8]) -> Result<(), MspErr> {
+    match sent == bufs.len() {
+        true => Ok(()),
+        false => Err(std::io::Error::new(
+            std::io::ErrorKind::WriteZero,
+            format!("Datagram partially sent: {} of {} bytes", sent, bufs.len()),
+        )
+        .into()),
+    }
+}
+
+/// Send `bufs` as a single datagram on the connected UDP socket, see [check_datagram_sent].
+pub fn send_datagram(socket: &UdpSocket, bufs: &[u8]) -> Result<(), MspErr> {
+    check_datagram_sent(socket.send(bufs)?, bufs)
+}
+
+/// Async version of [create_tcp_socket].
+///
+/// Tokio sockets have no built-in timeouts, use [timeout_async] to
+/// apply the [SocketConf] timeouts on each operation.
+#[cfg(feature = "tokio")]
+pub async fn create_tcp_socket_async(conf: &Conf) -> Result<tokio::net::TcpStream, MspErr> {
+    match &conf.proxy {
+        Some(proxy) => {
+            let addrs = resolve_host_async(
+                conf,
+                &proxy.host,
+                proxy.port,
+                conf.socket_conf.ip_preference,
+            )
+            .await?;
+            let mut socket = connect_tcp_async(
+                &addrs,
+                conf.socket_conf.connect_timeout,
+                conf.socket_conf.bind_addr,
+            )
+            .await?;
+
+            timeout_async(
+                conf.socket_conf.read_time_out,
+                crate::socks::socks5_connect_async(&mut socket, proxy, &conf.host, conf.port),
+            )
+            .await?;
+
+            Ok(socket)
+        }
+        None => {
+            let addrs = resolve_conf_addrs_async(conf, conf.socket_conf.ip_preference).await?;
+
+            connect_tcp_async(
+                &addrs,
+                conf.socket_conf.connect_timeout,
+                conf.socket_conf.bind_addr,
+            )
+            .await
+        }
+    }
+}
+
+/// Async version of [connect_tcp], the deadline is applied by [overall_timeout_async].
+#[cfg(feature = "tokio")]
+async fn connect_tcp_async(
+    addrs: &[SocketAddr],
+    connect_timeout: Option<Duration>,
+    bind_addr: Option<SocketAddr>,
+) -> Result<tokio::net::TcpStream, MspErr> {
+    let mut last_err = None;
+
+    for addr in addrs.iter().filter(|addr| is_same_family(bind_addr, addr)) {
+        match timeout_async(connect_timeout, connect_tcp_addr_async(addr, bind_addr)).await {
+            Ok(socket) => return Ok(socket),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(match last_err {
+        Some(err) => err,
+        None => no_addr_of_bind_family(bind_addr),
+    })
+}
+
+/// Async version of [connect_tcp_addr], without timeout.
+#[cfg(feature = "tokio")]
+async fn connect_tcp_addr_async(
+    addr: &SocketAddr,
+    bind_addr: Option<SocketAddr>,
+) -> std::io::Result<tokio::net::TcpStream> {
+    let bind_addr = match bind_addr {
+        Some(bind_addr) => bind_addr,
+        None => return tokio::net::TcpStream::connect(addr).await,
+    };
+    let socket = match addr {
+        SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+    };
+
+    socket.bind(bind_addr)?;
+    socket.connect(*addr).await
+}
+
+/// Async version of [resolve_conf_addrs].
+#[cfg(feature = "tokio")]
+pub async fn resolve_conf_addrs_async(
+    conf: &Conf,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match conf.resolved_addr {
+        Some(addr) => Ok(vec![addr]),
+        None => resolve_host_async(conf, &conf.host, conf.port, preference).await,
+    }
+}
+
+/// Async version of [resolve_host].
+#[cfg(feature = "tokio")]
+pub async fn resolve_host_async(
+    conf: &Conf,
+    host: &str,
+    port: u16,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match &conf.resolver {
+        Some(resolver) => check_resolved_addrs(host, resolver.resolve(host, port)?, preference),
+        None => resolve_addrs_async(host, port, preference).await,
+    }
+}
+
+/// Async version of [resolve_addrs].
+#[cfg(feature = "tokio")]
+pub async fn resolve_addrs_async(
+    host: &str,
+    port: u16,
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, MspErr> {
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => check_resolved_addrs(host, addrs.collect(), preference),
+        Err(err) => Err(MspErr::DnsErr(format!(
+            "Cannot resolve host {}: {}",
+            host, err
+        ))),
+    }
+}
+
+/// Async version of [create_udp_socket_for].
+#[cfg(feature = "tokio")]
+pub async fn create_udp_socket_for_async(
+    socket_conf: &SocketConf,
+    target: &SocketAddr,
+) -> Result<tokio::net::UdpSocket, MspErr> {
+    tokio::net::UdpSocket::bind(udp_bind_addr(socket_conf, target.is_ipv6()))
+        .await
+        .map_err(|err| map_udp_bind_err(socket_conf.rep_udp_port, err))
+}
+
+/// Async version of [send_datagram], failing if it is not sent within `write_timeout`.
+#[cfg(feature = "tokio")]
+pub async fn send_datagram_async(
+    socket: &tokio::net::UdpSocket,
+    bufs: &[u8],
+    write_timeout: Option<Duration>,
+) -> Result<(), MspErr> {
+    check_datagram_sent(timeout_async(write_timeout, socket.send(bufs)).await?, bufs)
+}
+
+/// Run the given socket operation, failing with [std::io::ErrorKind::TimedOut]
+/// if it does not complete within `duration`.
+#[cfg(feature = "tokio")]
+pub async fn timeout_async<T, E, F>(duration: Option<Duration>, future: F) -> Result<T, MspErr>
+where
+    F: Future<Output = Result<T, E>>,
+    MspErr: From<E>,
+{
+    match duration {
+        Some(d) => match tokio::time::timeout(d, future).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(MspErr::Timeout(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("Socket operation timed out after {:?}", d),
+            ))),
+        },
+        None => Ok(future.await?),
+    }
+}
+
+/// Run the whole exchange with a TCP-based server, failing with
+/// [std::io::ErrorKind::TimedOut] once [SocketConf::overall_timeout] is exceeded.
+#[cfg(feature = "tokio")]
+pub async fn overall_timeout_async<T, F>(conf: &Conf, future: F) -> Result<T, MspErr>
+where
+    F: Future<Output = Result<T, MspErr>>,
+{
+    match conf.socket_conf.overall_timeout {
+        Some(d) => match tokio::time::timeout(d, future).await {
+            Ok(result) => result,
+            Err(_) => Err(MspErr::Timeout(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Overall timeout of the status request exceeded",
+            ))),
+        },
+        None => future.await,
+    }
+}
+
+/// Write [ToJson::to_json_string] for [Display](std::fmt::Display), a serialization error
+/// is written as is instead of failing with an opaque [std::fmt::Error].
+pub fn display_json<T: ToJson>(value: &T, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match value.to_json_string() {
+        Ok(json) => f.write_str(&json),
+        Err(err) => write!(f, "<JSON serialization failed: {}>", err),
+    }
+}
+
+/// JSON output of the server info and query results, the pretty one is also
+/// their [Display](std::fmt::Display) output.
+///
+/// # Example
+///
+/// ```no_run
+/// use msp::{Conf, MspErr, ToJson};
+///
+/// fn main() -> Result<(), MspErr> {
+///     let info = Conf::create("www.example.com").get_server_status()?;
+///
+///     println!("{}", info.to_json(false)?);
+///
+///     Ok(())
+/// }
+/// ```
+pub trait ToJson: serde::Serialize {
+    /// JSON of the value, compact on a single line unless `pretty`.
+    fn to_json(&self, pretty: bool) -> Result<String, MspErr> {
+        Ok(match pretty {
+            true => serde_json::to_string_pretty(self),
+            false => serde_json::to_string(self),
+        }?)
+    }
+
+    /// Pretty JSON used by the [Display](std::fmt::Display) output, with the
+    /// serialization error if any.
+    fn to_json_string(&self) -> Result<String, MspErr> {
+        self.to_json(true)
+    }
+}
+
 pub fn bufs_to_utf16_str(bufs: &[u8]) -> Result<String, MspErr> {
     if bufs.len() % 2 != 0 {
         return Err(MspErr::DataErr(format!(
@@ -60,15 +696,102 @@ pub fn bufs_to_utf16_str(bufs: &[u8]) -> Result<String, MspErr> {
     ))
 }
 
-pub struct UdpReader {
-    socket: UdpSocket,
+/// Decode standard [base64](https://datatracker.ietf.org/doc/html/rfc4648#section-4) string.
+///
+/// Whitespace is ignored (some servers send the favicon with line breaks),
+/// and the trailing `=` padding is optional.
+pub fn decode_base64(str: &str) -> Result<Vec<u8>, MspErr> {
+    let mut result = Vec::with_capacity(str.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0u8;
+
+    for (idx, ch) in str
+        .trim_end_matches(['=', '\r', '\n', ' '])
+        .bytes()
+        .enumerate()
+    {
+        let value = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'\r' | b'\n' | b' ' | b'\t' => continue,
+            _ => {
+                return Err(MspErr::DataErr(format!(
+                    "Invalid base64 character: 0x{:02X} at index {}",
+                    ch, idx
+                )));
+            }
+        };
+
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+
+    // A single remaining character cannot form a byte.
+    if bits >= 6 {
+        return Err(MspErr::DataErr("Invalid base64 length.".into()));
+    }
+
+    Ok(result)
+}
+
+/// Strip the `0x00` padding from the end of a UDP payload, e.g. of a datagram received
+/// into a zeroed fixed-size buffer, or of a string field padded by the server.
+pub fn trim_trailing_nul(bufs: &[u8]) -> &[u8] {
+    let len = bufs
+        .iter()
+        .rposition(|&buf| buf != 0x00)
+        .map_or(0, |idx| idx + 1);
+
+    &bufs[..len]
+}
+
+/// Datagram source that can be peeked from the beginning without consuming it.
+///
+/// Implemented for [UdpSocket] and for an already received datagram ([`Vec<u8>`]),
+/// which allows the async paths to share the same parsing logic.
+pub trait UdpPeek {
+    fn peek(&self, bufs: &mut [u8]) -> std::io::Result<usize>;
+}
+
+impl UdpPeek for UdpSocket {
+    fn peek(&self, bufs: &mut [u8]) -> std::io::Result<usize> {
+        UdpSocket::peek(self, bufs)
+    }
+}
+
+impl UdpPeek for Vec<u8> {
+    fn peek(&self, bufs: &mut [u8]) -> std::io::Result<usize> {
+        let len = bufs.len().min(self.len());
+
+        bufs[..len].copy_from_slice(&self[..len]);
+
+        Ok(len)
+    }
+}
+
+/// Reader over a single UDP datagram.
+///
+/// The whole datagram is peeked once on the first read and kept in memory,
+/// subsequent reads only index into it.
+pub struct UdpReader<S: UdpPeek = UdpSocket> {
+    socket: S,
+    datagram: Option<Vec<u8>>,
     current_idx: usize,
 }
 
-impl UdpReader {
-    pub fn create_with_idx(socket: UdpSocket, current_idx: usize) -> Self {
+impl<S: UdpPeek> UdpReader<S> {
+    pub fn create_with_idx(socket: S, current_idx: usize) -> Self {
         Self {
             socket,
+            datagram: None,
             current_idx,
         }
     }
@@ -96,63 +819,70 @@ impl UdpReader {
         Ok(())
     }
 
-    pub fn read(&mut self, consume: bool) -> Result<u8, MspErr> {
-        let mut bufs = vec![0u8; self.current_idx + 1];
-
-        match self.socket.peek(&mut bufs) {
-            Ok(_) => {
-                if consume {
-                    self.current_idx += 1;
-                }
+    /// The whole datagram, peeked on the first call.
+    fn datagram(&mut self) -> Result<&[u8], MspErr> {
+        if self.datagram.is_none() {
+            let mut bufs = vec![0u8; UDP_MAX_DATAGRAM_SIZE];
+            let len = self.socket.peek(&mut bufs)?;
 
-                match bufs.last() {
-                    Some(&buf) => Ok(buf),
-                    None => {
-                        return Err(MspErr::DataErr("Incomplete data".into()));
-                    }
-                }
-            }
-            Err(err) => Err(MspErr::IoErr(err)),
+            bufs.truncate(len);
+            self.datagram = Some(bufs);
         }
+
+        Ok(self.datagram.as_deref().unwrap_or_default())
+    }
+
+    /// Number of bytes left in the datagram after the current index.
+    pub fn remaining(&mut self) -> Result<usize, MspErr> {
+        let current_idx = self.current_idx;
+
+        Ok(self.datagram()?.len().saturating_sub(current_idx))
+    }
+
+    /// Read `size` bytes from the current index without consuming them.
+    fn peek_bufs(&mut self, size: usize) -> Result<&[u8], MspErr> {
+        let current_idx = self.current_idx;
+
+        self.datagram()?
+            .get(current_idx..current_idx + size)
+            .ok_or_else(|| MspErr::DataErr("Incomplete data".into()))
     }
 
-    pub fn read_bufs(&mut self, mut size: usize) -> Result<Vec<u8>, MspErr> {
-        let mut buf_vec = Vec::new();
+    pub fn read(&mut self, consume: bool) -> Result<u8, MspErr> {
+        let buf = self.peek_bufs(1)?[0];
 
-        while size > 0 {
-            buf_vec.push(self.read(true)?);
-            size -= 1;
+        if consume {
+            self.current_idx += 1;
         }
 
-        Ok(buf_vec)
+        Ok(buf)
+    }
+
+    pub fn read_bufs(&mut self, size: usize) -> Result<Vec<u8>, MspErr> {
+        let bufs = self.peek_bufs(size)?.to_vec();
+
+        self.current_idx += size;
+
+        Ok(bufs)
     }
 
     pub fn read_nt_str(&mut self) -> Result<String, MspErr> {
         let mut result = Vec::new();
 
         loop {
-            let buf = self.read(true)?;
-            // Compatible with special characters: § © ®...
-            //
-            // FIXME This is only intended to address situations where the server.properties
-            // file is not properly formatted, and it does not cover all special characters
-            // larger than one byte. For more details, please refer to
-            // [UTF-8 encoding table and Unicode characters](https://www.utf8-chartable.de/unicode-utf8-table.pl)
-            match buf {
-                // Check the Null-terminated string
+            // Check the Null-terminated string
+            match self.read(true)? {
                 0x00 => break,
-                special_buf
-                    if special_buf >= 0x80
-                        && 0xBF >= special_buf
-                        && result.last() != Some(&0xC2) =>
-                {
-                    result.append(&mut vec![0xC2, special_buf]);
-                }
-                common_buf => result.push(common_buf),
+                buf => result.push(buf),
             }
         }
 
-        Ok(String::from_utf8_lossy(result.as_slice()).into())
+        match String::from_utf8(result) {
+            Ok(str) => Ok(str),
+            // Compatible with special characters (§ © ®...) of a server.properties
+            // file not saved as UTF-8, which are then sent as ISO-8859-1.
+            Err(err) => Ok(err.into_bytes().into_iter().map(char::from).collect()),
+        }
     }
 
     pub fn read_nt_str_group(&mut self) -> Result<Vec<String>, MspErr> {
@@ -164,15 +894,13 @@ impl UdpReader {
 
             match buf {
                 0x00 => {
-                    let next_buf = self.read(true)?;
-
-                    if next_buf == 0x00 {
+                    // An empty string terminates the group.
+                    if result.is_empty() {
                         break;
                     }
 
                     str_group.push(String::from_utf8_lossy(result.as_slice()).into());
                     result.clear();
-                    result.push(next_buf);
                 }
                 common_buf => result.push(common_buf),
             }
@@ -180,43 +908,60 @@ impl UdpReader {
 
         Ok(str_group)
     }
+}
 
-    pub fn read_nt_kv(&mut self) -> Result<(String, String), MspErr> {
-        let mut result = Vec::new();
-        let mut kv: (Option<String>, Option<String>) = (None, None);
+#[cfg(test)]
+mod share_test {
+    use super::{
+        bufs_to_utf16_str, check_resolved_addrs, connect_tcp, connect_time_limit,
+        create_udp_socket_for, decode_base64, display_json, resolve_addrs, retry,
+        trim_trailing_nul, ToJson, UdpReader,
+    };
+    use crate::{IpPreference, MspErr, SocketConf};
+    use std::{
+        net::{SocketAddr, TcpListener},
+        time::{Duration, Instant},
+    };
 
-        loop {
-            let buf = self.read(true)?;
+    #[test]
+    fn test_display_json() {
+        // Tuple keys cannot be serialized into JSON object keys.
+        #[derive(serde::Serialize)]
+        struct Json(std::collections::HashMap<(u8, u8), u8>);
 
-            match buf {
-                0x00 => {
-                    if kv.0.is_none() {
-                        kv.0 = Some(String::from_utf8_lossy(result.as_slice()).into());
-                        result.clear();
-                        continue;
-                    }
+        impl ToJson for Json {}
 
-                    kv.1 = Some(String::from_utf8_lossy(result.as_slice()).into());
-                    break;
-                }
-                special_buf
-                    if special_buf >= 0x80
-                        && 0xBF >= special_buf
-                        && result.last() != Some(&0xC2) =>
-                {
-                    result.append(&mut vec![0xC2, special_buf]);
-                }
-                common_buf => result.push(common_buf),
+        impl std::fmt::Display for Json {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                display_json(self, f)
             }
         }
 
-        Ok((kv.0.unwrap_or("".into()), kv.1.unwrap_or("".into())))
+        assert_eq!(Json(Default::default()).to_string(), "{}");
+        assert_eq!(
+            Json([((0, 0), 0)].into()).to_string(),
+            "<JSON serialization failed: key must be a string>"
+        );
     }
-}
 
-#[cfg(test)]
-mod share_test {
-    use super::bufs_to_utf16_str;
+    #[test]
+    fn test_create_udp_socket_for() {
+        let socket_conf = SocketConf {
+            rep_udp_port: 0,
+            ..Default::default()
+        };
+        let local_addr = |target: &str| {
+            create_udp_socket_for(&socket_conf, &target.parse().unwrap())
+                .and_then(|socket| Ok(socket.local_addr()?))
+        };
+
+        assert!(local_addr("127.0.0.1:19132").unwrap().is_ipv4());
+
+        // IPv6 may be disabled on the host.
+        if let Ok(addr) = local_addr("[::1]:19132") {
+            assert!(addr.is_ipv6());
+        }
+    }
 
     #[test]
     fn test_bufs_to_utf16_str() {
@@ -226,4 +971,121 @@ mod share_test {
 
         assert!(bufs_to_utf16_str(&[0, 104, 0, 101, 0, 108, 0]).is_err());
     }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8h").unwrap(), b"hello!");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert!(decode_base64("aGVs*G8=").is_err());
+        assert!(decode_base64("aGVsb").is_err());
+    }
+
+    #[test]
+    fn test_udp_reader() {
+        let mut reader = UdpReader::create_with_idx(b"\x00\x01key\x00value\x00\x02".to_vec(), 1);
+
+        assert_eq!(reader.read(false).unwrap(), 0x01);
+        assert_eq!(reader.read_bufs(1).unwrap(), vec![0x01]);
+        assert_eq!(reader.read_nt_str().unwrap(), "key");
+        assert_eq!(reader.read_nt_str().unwrap(), "value");
+        assert_eq!(reader.read(true).unwrap(), 0x02);
+        assert!(reader.read(true).is_err());
+
+        let mut bufs = "§a服务器 🎮\0".as_bytes().to_vec();
+
+        // ISO-8859-1 `§b`
+        bufs.extend_from_slice(b"\xA7b\0");
+
+        let mut reader = UdpReader::create_with_idx(bufs, 0);
+
+        assert_eq!(reader.read_nt_str().unwrap(), "§a服务器 🎮");
+        assert_eq!(reader.read_nt_str().unwrap(), "§b");
+
+        assert_eq!(trim_trailing_nul(b"\x09\x00\x01\x00\x00"), b"\x09\x00\x01");
+        assert_eq!(trim_trailing_nul(b"\x00\x00"), b"");
+    }
+
+    #[test]
+    fn test_resolve_addrs_preference() {
+        let addrs = resolve_addrs("::1", 25565, IpPreference::Ipv4).unwrap();
+
+        assert!(addrs[0].is_ipv6());
+
+        let mut addrs = vec![
+            SocketAddr::from(([127, 0, 0, 1], 25565)),
+            SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 25565)),
+            SocketAddr::from(([127, 0, 0, 2], 25565)),
+        ];
+
+        addrs = check_resolved_addrs("localhost", addrs, IpPreference::Ipv6).unwrap();
+
+        assert!(addrs[0].is_ipv6());
+        assert_eq!(addrs[1], SocketAddr::from(([127, 0, 0, 1], 25565)));
+        assert!(check_resolved_addrs("localhost", vec![], IpPreference::Resolver).is_err());
+    }
+
+    #[test]
+    fn test_connect_tcp_bind_addr() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addrs = [listener.local_addr().unwrap()];
+        let bind_addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let socket = connect_tcp(&addrs, None, None, Some(bind_addr)).unwrap();
+
+        assert_eq!(socket.local_addr().unwrap().ip(), bind_addr.ip());
+        assert!(connect_tcp(&addrs, None, None, Some("[::1]:0".parse().unwrap())).is_err());
+    }
+
+    #[test]
+    fn test_connect_time_limit() {
+        let second = Duration::from_secs(1);
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        assert_eq!(connect_time_limit(None, None).unwrap(), None);
+        assert_eq!(
+            connect_time_limit(None, Some(second)).unwrap(),
+            Some(second)
+        );
+        assert_eq!(
+            connect_time_limit(Some(deadline), Some(second)).unwrap(),
+            Some(second)
+        );
+        assert!(connect_time_limit(Some(deadline), None).unwrap().unwrap() > second);
+        assert!(connect_time_limit(Some(Instant::now()), Some(second)).is_err());
+    }
+
+    #[test]
+    fn test_retry() {
+        let socket_conf = SocketConf {
+            max_retries: 2,
+            retry_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let failing = |failures: u32| {
+            let mut attempts = 0;
+
+            move || {
+                attempts += 1;
+
+                match attempts > failures {
+                    true => Ok(attempts),
+                    false => Err(MspErr::IoErr(std::io::ErrorKind::ConnectionReset.into())),
+                }
+            }
+        };
+
+        assert_eq!(retry(&socket_conf, failing(2)).unwrap(), 3);
+        assert!(retry(&socket_conf, failing(3)).is_err());
+        assert!(retry(&SocketConf::default(), failing(1)).is_err());
+        // Non-transient errors are not retried.
+        let mut attempts = 0;
+
+        assert!(retry(&socket_conf, || {
+            attempts += 1;
+            Err::<(), _>(MspErr::DataErr("".into()))
+        })
+        .is_err());
+        assert_eq!(attempts, 1);
+    }
 }
@@ -0,0 +1,227 @@
+use crate::{MspErr, ProxyConf};
+use std::io::{Read, Write};
+
+const SOCKS_VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xFF;
+/// Version of the [username/password](https://datatracker.ietf.org/doc/html/rfc1929) sub-negotiation.
+const AUTH_VERSION: u8 = 0x01;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN_NAME: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Establish a [SOCKS5](https://datatracker.ietf.org/doc/html/rfc1928) tunnel to `host:port`
+/// over a socket already connected to the proxy.
+///
+/// The host is sent as a domain name, so it is resolved by the proxy.
+pub fn socks5_connect<S: Read + Write>(
+    socket: &mut S,
+    proxy: &ProxyConf,
+    host: &str,
+    port: u16,
+) -> Result<(), MspErr> {
+    let mut bufs = [0u8; 2];
+
+    socket.write_all(&build_greeting_packet(proxy))?;
+    socket.read_exact(&mut bufs)?;
+
+    if check_greeting_response(bufs)? == METHOD_USERNAME_PASSWORD {
+        socket.write_all(&build_auth_packet(proxy)?)?;
+        socket.read_exact(&mut bufs)?;
+        check_auth_response(bufs)?;
+    }
+
+    let mut bufs = [0u8; 5];
+
+    socket.write_all(&build_connect_packet(host, port)?)?;
+    socket.read_exact(&mut bufs)?;
+
+    // Drop the bound address, it is of no use for the client.
+    let mut bound_addr = vec![0u8; check_connect_response(bufs)?];
+
+    socket.read_exact(&mut bound_addr)?;
+
+    Ok(())
+}
+
+/// Async version of [socks5_connect].
+#[cfg(feature = "tokio")]
+pub async fn socks5_connect_async(
+    socket: &mut tokio::net::TcpStream,
+    proxy: &ProxyConf,
+    host: &str,
+    port: u16,
+) -> Result<(), MspErr> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut bufs = [0u8; 2];
+
+    socket.write_all(&build_greeting_packet(proxy)).await?;
+    socket.read_exact(&mut bufs).await?;
+
+    if check_greeting_response(bufs)? == METHOD_USERNAME_PASSWORD {
+        socket.write_all(&build_auth_packet(proxy)?).await?;
+        socket.read_exact(&mut bufs).await?;
+        check_auth_response(bufs)?;
+    }
+
+    let mut bufs = [0u8; 5];
+
+    socket.write_all(&build_connect_packet(host, port)?).await?;
+    socket.read_exact(&mut bufs).await?;
+
+    let mut bound_addr = vec![0u8; check_connect_response(bufs)?];
+
+    socket.read_exact(&mut bound_addr).await?;
+
+    Ok(())
+}
+
+/// Build greeting packet, offering username/password authentication only if credentials are set.
+fn build_greeting_packet(proxy: &ProxyConf) -> Vec<u8> {
+    match proxy.credentials {
+        Some(_) => vec![SOCKS_VERSION, 2, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD],
+        None => vec![SOCKS_VERSION, 1, METHOD_NO_AUTH],
+    }
+}
+
+/// Check greeting response and return the method chosen by the proxy.
+fn check_greeting_response(bufs: [u8; 2]) -> Result<u8, MspErr> {
+    match bufs {
+        [SOCKS_VERSION, method @ (METHOD_NO_AUTH | METHOD_USERNAME_PASSWORD)] => Ok(method),
+        [SOCKS_VERSION, METHOD_NO_ACCEPTABLE] => Err(MspErr::DataErr(
+            "SOCKS5 proxy rejected all offered authentication methods.".into(),
+        )),
+        _ => Err(MspErr::DataErr(format!(
+            "Invalid SOCKS5 greeting response: {:?}",
+            bufs
+        ))),
+    }
+}
+
+fn build_auth_packet(proxy: &ProxyConf) -> Result<Vec<u8>, MspErr> {
+    let (username, password) = match &proxy.credentials {
+        Some(credentials) => credentials,
+        None => {
+            return Err(MspErr::DataErr(
+                "SOCKS5 proxy requires authentication, but no credentials are set.".into(),
+            ));
+        }
+    };
+    let mut packet = vec![AUTH_VERSION];
+
+    for field in [username, password] {
+        if field.len() > u8::MAX as usize {
+            return Err(MspErr::DataErr(
+                "SOCKS5 username and password cannot be longer than 255 bytes.".into(),
+            ));
+        }
+
+        packet.push(field.len() as u8);
+        packet.extend_from_slice(field.as_bytes());
+    }
+
+    Ok(packet)
+}
+
+fn check_auth_response(bufs: [u8; 2]) -> Result<(), MspErr> {
+    match bufs {
+        [AUTH_VERSION, 0x00] => Ok(()),
+        _ => Err(MspErr::DataErr(
+            "SOCKS5 proxy authentication failed.".into(),
+        )),
+    }
+}
+
+fn build_connect_packet(host: &str, port: u16) -> Result<Vec<u8>, MspErr> {
+    if host.is_empty() || host.len() > u8::MAX as usize {
+        return Err(MspErr::DataErr(format!(
+            "Invalid host for SOCKS5 proxy: {}",
+            host
+        )));
+    }
+
+    let mut packet = vec![SOCKS_VERSION, CMD_CONNECT, 0x00, ATYP_DOMAIN_NAME];
+
+    packet.push(host.len() as u8);
+    packet.extend_from_slice(host.as_bytes());
+    packet.extend_from_slice(&port.to_be_bytes());
+
+    Ok(packet)
+}
+
+/// Check the first 5 bytes of the connect response, and return the
+/// length of the remaining bound address and port.
+fn check_connect_response(bufs: [u8; 5]) -> Result<usize, MspErr> {
+    if bufs[0] != SOCKS_VERSION {
+        return Err(MspErr::DataErr(format!(
+            "Invalid SOCKS5 connect response version: 0x{:02X}",
+            bufs[0]
+        )));
+    }
+
+    if bufs[1] != 0x00 {
+        return Err(MspErr::DataErr(format!(
+            "SOCKS5 proxy failed to connect: {}",
+            match bufs[1] {
+                0x01 => "general SOCKS server failure",
+                0x02 => "connection not allowed by ruleset",
+                0x03 => "network unreachable",
+                0x04 => "host unreachable",
+                0x05 => "connection refused",
+                0x06 => "TTL expired",
+                0x07 => "command not supported",
+                0x08 => "address type not supported",
+                _ => "unknown error",
+            }
+        )));
+    }
+
+    // The 5th byte is the first byte of the bound address, or its length for domain name.
+    match bufs[3] {
+        ATYP_IPV4 => Ok(4 - 1 + 2),
+        ATYP_IPV6 => Ok(16 - 1 + 2),
+        ATYP_DOMAIN_NAME => Ok(bufs[4] as usize + 2),
+        atyp => Err(MspErr::DataErr(format!(
+            "Invalid SOCKS5 address type: 0x{:02X}",
+            atyp
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod socks_test {
+    use super::*;
+
+    #[test]
+    fn test_socks5_packets() {
+        let proxy = ProxyConf {
+            host: "127.0.0.1".into(),
+            port: 1080,
+            credentials: Some(("user".into(), "pass".into())),
+        };
+
+        assert_eq!(build_greeting_packet(&proxy), vec![0x05, 2, 0x00, 0x02]);
+        assert_eq!(
+            build_auth_packet(&proxy).unwrap(),
+            b"\x01\x04user\x04pass".to_vec()
+        );
+        assert_eq!(
+            build_connect_packet("mc.example.com", 25565).unwrap(),
+            b"\x05\x01\x00\x03\x0Emc.example.com\x63\xDD".to_vec()
+        );
+        assert_eq!(check_greeting_response([0x05, 0x00]).unwrap(), 0x00);
+        assert!(check_greeting_response([0x05, 0xFF]).is_err());
+        assert_eq!(
+            check_connect_response([0x05, 0x00, 0x00, 0x01, 0]).unwrap(),
+            5
+        );
+        assert_eq!(
+            check_connect_response([0x05, 0x00, 0x00, 0x03, 9]).unwrap(),
+            11
+        );
+        assert!(check_connect_response([0x05, 0x05, 0x00, 0x01, 0]).is_err());
+    }
+}
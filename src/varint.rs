@@ -1,5 +1,5 @@
 use crate::MspErr;
-use std::{io::Read, net::TcpStream};
+use std::io::Read;
 
 const SEGMENT_BITS: u32 = 0x7F;
 const CHECKER_BIT: u8 = 0x80;
@@ -27,7 +27,7 @@ pub fn encode_varint(num: i32) -> Vec<u8> {
 }
 
 /// Decode the given VarInt as a number
-pub fn decode_varint(arr: &Vec<u8>) -> Result<i32, MspErr> {
+pub fn decode_varint(arr: &[u8]) -> Result<i32, MspErr> {
     // VarInts are never longer than 5 bytes
     //
     // Because VarInt encoding objects are of type i32,
@@ -63,19 +63,108 @@ pub fn decode_varint(arr: &Vec<u8>) -> Result<i32, MspErr> {
     }
 }
 
-pub fn decode_varint_from_socket(socket: &mut TcpStream) -> Result<(usize, i32), MspErr> {
-    let mut buffer = Vec::<u8>::new();
-    let mut temp_buffer = vec![0; 1];
+/// Encode the given number as a [VarLong](https://wiki.vg/Protocol#VarInt_and_VarLong).
+///
+/// Same as [encode_varint], negative values always take the maximum of 10 bytes.
+#[allow(dead_code)]
+pub fn encode_varlong(num: i64) -> Vec<u8> {
+    let mut num = num as u64;
+    let mut result = Vec::<u8>::new();
 
     loop {
-        socket.read(&mut temp_buffer)?;
+        if (num & !(SEGMENT_BITS as u64)) == 0 {
+            result.push(num as u8);
+
+            return result;
+        }
+
+        result.push(((num & SEGMENT_BITS as u64) | CHECKER_BIT as u64) as u8);
+        num >>= 7;
+    }
+}
+
+/// Decode the given VarLong as a number
+#[allow(dead_code)]
+pub fn decode_varlong(arr: &[u8]) -> Result<i64, MspErr> {
+    // VarLongs are never longer than 10 bytes
+    if arr.len() > 10 {
+        return Err(MspErr::DataErr(format!(
+            "VarLongs are never longer than 10 bytes, but got {}",
+            arr.len()
+        )));
+    }
 
-        if let Some(&buf) = temp_buffer.get(0) {
-            buffer.push(buf);
+    match arr.last() {
+        Some(&n) => {
+            if n & CHECKER_BIT != 0 {
+                return Err(MspErr::DataErr(format!("Invalid VarLong data: {:?}", arr)));
+            }
 
-            if buf & CHECKER_BIT == 0 {
-                break;
+            let mut result = 0i64;
+
+            for (i, &n) in arr.iter().enumerate() {
+                result |= ((n as i64) & (SEGMENT_BITS as i64)) << (i * 7);
             }
+
+            Ok(result)
+        }
+        None => Err(MspErr::DataErr("VarLong is empty".into())),
+    }
+}
+
+/// Decode the VarInt at the start of the given buffers.
+///
+/// Return the number of bytes the VarInt takes and the decoded number.
+pub fn decode_varint_from_bufs(bufs: &[u8]) -> Result<(usize, i32), MspErr> {
+    match bufs.iter().position(|&buf| buf & CHECKER_BIT == 0) {
+        Some(idx) => Ok((idx + 1, decode_varint(&bufs[..=idx])?)),
+        None => Err(MspErr::DataErr("Incomplete VarInt data".into())),
+    }
+}
+
+/// Decode the VarInt read byte by byte from the given reader.
+///
+/// Return the number of bytes the VarInt takes and the decoded number.
+pub fn decode_varint_from_reader<R: Read>(reader: &mut R) -> Result<(usize, i32), MspErr> {
+    let mut buffer = Vec::<u8>::new();
+    let mut temp_buffer = [0u8; 1];
+
+    // Stop after 5 bytes, which [decode_varint] rejects, rather than reading forever.
+    while buffer.len() <= 5 {
+        if reader.read(&mut temp_buffer)? == 0 {
+            return Err(MspErr::DataErr(
+                "unexpected EOF while decoding VarInt".into(),
+            ));
+        }
+
+        buffer.push(temp_buffer[0]);
+
+        if temp_buffer[0] & CHECKER_BIT == 0 {
+            break;
+        }
+    }
+
+    Ok((buffer.len(), decode_varint(&buffer)?))
+}
+
+/// Async version of [decode_varint_from_reader].
+#[cfg(feature = "tokio")]
+pub async fn decode_varint_from_async_socket<R>(socket: &mut R) -> Result<(usize, i32), MspErr>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = Vec::<u8>::new();
+
+    // See [decode_varint_from_reader].
+    while buffer.len() <= 5 {
+        let buf = socket.read_u8().await?;
+
+        buffer.push(buf);
+
+        if buf & CHECKER_BIT == 0 {
+            break;
         }
     }
 
@@ -154,4 +243,59 @@ mod varint_test {
         assert!(decode_result.is_ok());
         assert_eq!(decode_result.unwrap(), -2147483648);
     }
+
+    #[test]
+    fn test_varlong() {
+        for (num, bufs) in [
+            (0i64, vec![0x00]),
+            (1, vec![0x01]),
+            (2, vec![0x02]),
+            (127, vec![0x7F]),
+            (128, vec![0x80, 0x01]),
+            (255, vec![0xFF, 0x01]),
+            (2147483647, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+            (
+                9223372036854775807,
+                vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F],
+            ),
+            (
+                -1,
+                vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+            ),
+            (
+                -2147483648,
+                vec![0x80, 0x80, 0x80, 0x80, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+            ),
+            (
+                -9223372036854775808,
+                vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+            ),
+        ] {
+            assert_eq!(encode_varlong(num), bufs);
+            assert_eq!(decode_varlong(&bufs).unwrap(), num);
+        }
+
+        assert!(decode_varlong(&[0x80]).is_err());
+        assert!(decode_varlong(&[0x80; 11]).is_err());
+    }
+
+    #[test]
+    fn test_decode_varint_from_reader() {
+        let mut reader = std::io::Cursor::new(vec![0xDD, 0xC7, 0x01, 0x7F]);
+
+        assert_eq!(decode_varint_from_reader(&mut reader).unwrap(), (3, 25565));
+        assert_eq!(decode_varint_from_reader(&mut reader).unwrap(), (1, 127));
+
+        // Connection closed mid-VarInt.
+        let mut reader = std::io::Cursor::new(vec![0xDD, 0xC7]);
+
+        match decode_varint_from_reader(&mut reader) {
+            Err(MspErr::DataErr(msg)) => assert!(msg.contains("unexpected EOF")),
+            result => panic!("Expected DataErr, but got: {:?}", result),
+        }
+
+        let mut reader = std::io::Cursor::new(vec![0xFF; 16]);
+
+        assert!(decode_varint_from_reader(&mut reader).is_err());
+    }
 }